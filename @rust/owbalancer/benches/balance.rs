@@ -0,0 +1,124 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use owbalancer::generator::LobbyGenerator;
+use owbalancer::matchmaking::{Matchmaking, Phase};
+use owbalancer::players::Players;
+
+const SIZES: [usize; 3] = [60, 200, 400];
+
+fn lobby(size: usize) -> Players {
+    LobbyGenerator {
+        size,
+        seed: Some(size as u64),
+        ..LobbyGenerator::default()
+    }
+    .generate()
+}
+
+fn matchmaking(players: &Players) -> Matchmaking {
+    let mut matchmaking = Matchmaking::new(players, 50, true, true);
+    matchmaking.set_seed(1);
+    matchmaking.enable_dispersion_minimizer();
+    matchmaking
+}
+
+fn balance_players(c: &mut Criterion) {
+    let mut group = c.benchmark_group("balance_players");
+
+    for &size in SIZES.iter() {
+        let players = lobby(size);
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &players, |b, players| {
+            b.iter_batched(
+                || matchmaking(players),
+                |mut matchmaking| {
+                    matchmaking.balance_players();
+                    matchmaking.result()
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+fn balance_half_and_remaining(c: &mut Criterion) {
+    let mut group = c.benchmark_group("balance_half");
+
+    for &size in SIZES.iter() {
+        let players = lobby(size);
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &players, |b, players| {
+            b.iter_batched(
+                || matchmaking(players),
+                |mut matchmaking| {
+                    matchmaking.balance_half();
+                    matchmaking.result()
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+
+    let mut group = c.benchmark_group("balance_remaining");
+
+    for &size in SIZES.iter() {
+        let players = lobby(size);
+        let mut half = matchmaking(&players);
+        half.balance_half();
+        let half = half.result();
+        let reserve = half.leftovers.collect_ids();
+
+        group.bench_with_input(BenchmarkId::from_parameter(size), &players, |b, players| {
+            b.iter_batched(
+                || {
+                    let mut matchmaking = matchmaking(players);
+                    matchmaking.add_reserve(reserve.clone());
+                    matchmaking.add_teams(half.teams.clone());
+                    matchmaking
+                },
+                |mut matchmaking| {
+                    matchmaking.balance_remaining();
+                    matchmaking.result()
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// Every phase starts from the state the previous phases left behind.
+fn phases(c: &mut Criterion) {
+    let players = lobby(200);
+    let mut group = c.benchmark_group("phases");
+
+    for (index, &phase) in Phase::ALL.iter().enumerate() {
+        group.bench_function(phase.label(), |b| {
+            b.iter_batched(
+                || {
+                    let mut matchmaking = matchmaking(&players);
+
+                    for &previous in &Phase::ALL[..index] {
+                        matchmaking.run_phase(previous);
+                    }
+
+                    matchmaking
+                },
+                |mut matchmaking| {
+                    matchmaking.run_phase(phase);
+                    matchmaking
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, balance_players, balance_half_and_remaining, phases);
+criterion_main!(benches);
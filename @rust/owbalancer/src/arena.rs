@@ -0,0 +1,183 @@
+use crate::history::TeammateHistory;
+use crate::players::{Candidate, PlayerPool, Players};
+use crate::teams::{Team, Teams};
+use std::collections::HashMap;
+
+/// Index of a player that is not part of the lobby, e.g. a stale member sent back by the UI.
+pub const NO_PLAYER: usize = usize::MAX;
+
+/// Index of players that were not placed in an arena, e.g. deserialized ones.
+pub fn no_player() -> usize {
+    NO_PLAYER
+}
+
+/// Lobby players by dense index. Candidates and members carry the index of their
+/// player, so balancing looks players up without hashing or comparing uuids.
+#[derive(Debug, Default)]
+pub struct Arena {
+    candidates: Vec<Candidate>,
+    ids: HashMap<String, usize>,
+    captains: Vec<bool>,
+    squires: Vec<bool>,
+    /// `mates[a * len + b]` is the number of games `a` and `b` played together,
+    /// empty without history.
+    mates: Vec<u32>,
+}
+
+impl Arena {
+    pub fn new(players: &Players) -> Arena {
+        let mut arena = Arena::default();
+
+        for player in players.0.values() {
+            let mut candidate = Candidate::from(player);
+            candidate.index = arena.candidates.len();
+
+            arena.ids.insert(candidate.uuid.clone(), candidate.index);
+            arena.captains.push(player.identity.is_captain);
+            arena.squires.push(player.identity.is_squire);
+            arena.candidates.push(candidate);
+        }
+
+        arena
+    }
+
+    pub fn len(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.candidates.is_empty()
+    }
+
+    pub fn index_of(&self, uuid: &str) -> usize {
+        self.ids.get(uuid).copied().unwrap_or(NO_PLAYER)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Candidate> {
+        self.candidates.get(index)
+    }
+
+    /// Captains and squires, they are never swapped between teams.
+    pub fn is_anchor(&self, index: usize) -> bool {
+        self.captains.get(index) == Some(&true) || self.squires.get(index) == Some(&true)
+    }
+
+    /// Teammate counts of lobby players, pairs with players outside of the lobby are dropped.
+    pub fn set_history(&mut self, history: &TeammateHistory) {
+        let len = self.len();
+        self.mates = Vec::default();
+
+        if history.pairs.is_empty() {
+            return;
+        }
+
+        self.mates = vec![0; len * len];
+
+        for (uuid, mates) in &history.pairs {
+            let index = self.index_of(uuid);
+
+            if index == NO_PLAYER {
+                continue;
+            }
+
+            for (mate, count) in mates {
+                let mate = self.index_of(mate);
+
+                if mate != NO_PLAYER {
+                    self.mates[index * len + mate] = *count;
+                }
+            }
+        }
+    }
+
+    pub fn count(&self, index: usize, mate: usize) -> u32 {
+        let len = self.len();
+
+        if index >= len || mate >= len {
+            return 0;
+        }
+
+        self.mates.get(index * len + mate).copied().unwrap_or(0)
+    }
+
+    /// Same as `TeammateHistory::repeats_with`, by player index.
+    pub fn repeats_with(&self, team: &Team, index: usize) -> u32 {
+        if self.mates.is_empty() {
+            return 0;
+        }
+
+        team.members
+            .iter()
+            .filter(|member| member.index != index)
+            .map(|member| self.count(index, member.index))
+            .sum()
+    }
+
+    /// Sets member indices of teams balanced elsewhere, e.g. sent back by the UI.
+    pub fn index_teams(&self, teams: &mut Teams) {
+        for team in &mut teams.0 {
+            for member in &mut team.members {
+                member.index = self.index_of(&member.uuid);
+            }
+        }
+    }
+
+    pub fn get_captains(&self) -> PlayerPool {
+        self.filter(&self.captains)
+    }
+
+    pub fn get_squires(&self) -> PlayerPool {
+        self.filter(&self.squires)
+    }
+
+    /// Adds players that are not in `exclude` to the pool, or only those in it when inverted.
+    pub fn feed(&self, pool: &mut PlayerPool, exclude: &[String], invert: bool) {
+        let mut excluded = vec![false; self.len()];
+
+        for uuid in exclude {
+            if let Some(&index) = self.ids.get(uuid) {
+                excluded[index] = true;
+            }
+        }
+
+        for candidate in &self.candidates {
+            if excluded[candidate.index] == invert {
+                pool.add_candidate(candidate);
+            }
+        }
+    }
+
+    fn filter(&self, flags: &[bool]) -> PlayerPool {
+        PlayerPool(
+            self.candidates
+                .iter()
+                .filter(|candidate| flags[candidate.index])
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Pool players as they were before distribution, replacements are taken from them.
+#[derive(Debug, Default, Clone)]
+pub struct Reserve(Vec<bool>);
+
+impl Reserve {
+    pub fn new(pool: &PlayerPool, arena: &Arena) -> Reserve {
+        let mut reserve = vec![false; arena.len()];
+
+        for candidate in &pool.0 {
+            reserve[candidate.index] = true;
+        }
+
+        Reserve(reserve)
+    }
+
+    pub fn get<'a>(&self, arena: &'a Arena, index: usize) -> Option<&'a Candidate> {
+        if self.0.get(index) == Some(&true) {
+            arena.get(index)
+        } else {
+            None
+        }
+    }
+}
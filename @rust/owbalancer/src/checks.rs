@@ -0,0 +1,252 @@
+use crate::matchmaking::{BalancerResult, Config};
+use crate::players::{Player, Players};
+use crate::roles::{SimpleRole, SubRole};
+use crate::teams::{Member, Team};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Broken invariant of a balancer result. Teams are referenced by name.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Violation {
+    /// Player placed more than once, counting leftovers.
+    DuplicatePlayer {
+        uuid: String,
+    },
+    UnknownPlayer {
+        uuid: String,
+    },
+    /// Player with an active role neither in a team nor a leftover.
+    MissingPlayer {
+        uuid: String,
+    },
+    InactiveRole {
+        uuid: String,
+        role: SimpleRole,
+    },
+    TeamTooLarge {
+        team: String,
+        size: usize,
+    },
+    RoleOverLimit {
+        team: String,
+        role: SimpleRole,
+        count: usize,
+    },
+    /// Team not led by a captain, `uuid` is the member in the captain spot.
+    NoCaptain {
+        team: String,
+        uuid: String,
+    },
+    ExtraCaptain {
+        team: String,
+        uuid: String,
+    },
+    LeftoverCaptain {
+        uuid: String,
+    },
+    /// `total_sr` or `avg_sr` does not match the members.
+    InconsistentRating {
+        team: String,
+    },
+    /// Several members below the limiter rank share a role while the rank limiter is on.
+    RankLimit {
+        team: String,
+        role: SimpleRole,
+    },
+    /// Specialized sub role taken twice while duplicate roles are disallowed.
+    #[serde(rename_all = "camelCase")]
+    DuplicateSubRole {
+        team: String,
+        role: SimpleRole,
+        sub_role: SubRole,
+    },
+}
+
+/// Invariants every result has to keep, checked against the players and the config it
+/// was balanced with. The rank limiter and duplicate roles are checked as the caller asked
+/// for them, unless the result reports balancing relaxed them to fill teams. Captains and
+/// squires are placed before either rule applies, so pairs of them do not count as violations.
+pub fn check_result(players: &Players, config: &Config, result: &BalancerResult) -> Vec<Violation> {
+    let mut violations = Vec::default();
+    let mut placed = HashSet::new();
+
+    for team in &result.teams.0 {
+        for member in &team.members {
+            if !placed.insert(member.uuid.as_str()) {
+                violations.push(Violation::DuplicatePlayer {
+                    uuid: member.uuid.clone(),
+                });
+            }
+
+            check_member(players, member, &mut violations);
+        }
+
+        check_team(players, config, result, team, &mut violations);
+    }
+
+    for candidate in &result.leftovers.0 {
+        if !placed.insert(candidate.uuid.as_str()) {
+            violations.push(Violation::DuplicatePlayer {
+                uuid: candidate.uuid.clone(),
+            });
+        }
+
+        if is_captain(players.get_player(&candidate.uuid)) {
+            violations.push(Violation::LeftoverCaptain {
+                uuid: candidate.uuid.clone(),
+            });
+        }
+    }
+
+    for player in players.0.values() {
+        if is_active(player) && !placed.contains(player.identity.uuid.as_str()) {
+            violations.push(Violation::MissingPlayer {
+                uuid: player.identity.uuid.clone(),
+            });
+        }
+    }
+
+    violations
+}
+
+fn is_active(player: &Player) -> bool {
+    player.stats.classes.0.values().any(|class| class.is_active)
+}
+
+fn is_captain(player: Option<&Player>) -> bool {
+    player.is_some_and(|player| player.identity.is_captain)
+}
+
+fn is_anchor(players: &Players, member: &Member) -> bool {
+    players
+        .get_player(&member.uuid)
+        .is_some_and(|player| player.identity.is_captain || player.identity.is_squire)
+}
+
+fn check_member(players: &Players, member: &Member, violations: &mut Vec<Violation>) {
+    let player = match players.get_player(&member.uuid) {
+        Some(player) => player,
+        None => {
+            violations.push(Violation::UnknownPlayer {
+                uuid: member.uuid.clone(),
+            });
+            return;
+        }
+    };
+
+    let is_active = player
+        .stats
+        .classes
+        .get_class(&member.role)
+        .is_some_and(|class| class.is_active);
+
+    if !is_active {
+        violations.push(Violation::InactiveRole {
+            uuid: member.uuid.clone(),
+            role: member.role.clone(),
+        });
+    }
+}
+
+fn check_team(
+    players: &Players,
+    config: &Config,
+    result: &BalancerResult,
+    team: &Team,
+    violations: &mut Vec<Violation>,
+) {
+    let name = || team.name.clone();
+
+    if team.members_count() > config.composition.team_size() {
+        violations.push(Violation::TeamTooLarge {
+            team: name(),
+            size: team.members_count(),
+        });
+    }
+
+    for (position, member) in team.members.iter().enumerate() {
+        let player = players.get_player(&member.uuid);
+
+        if position == 0 && !is_captain(player) {
+            violations.push(Violation::NoCaptain {
+                team: name(),
+                uuid: member.uuid.clone(),
+            });
+        } else if position > 0 && is_captain(player) {
+            violations.push(Violation::ExtraCaptain {
+                team: name(),
+                uuid: member.uuid.clone(),
+            });
+        }
+    }
+
+    let total_sr: i32 = team
+        .members
+        .iter()
+        .map(|member| member.strength(config))
+        .sum();
+    let avg_sr = total_sr as f32 / team.members_count().max(1) as f32;
+
+    if team.total_sr != total_sr || (team.avg_sr - avg_sr).abs() > 0.01 {
+        violations.push(Violation::InconsistentRating { team: name() });
+    }
+
+    let mut roles: Vec<&SimpleRole> = team.members.iter().map(|member| &member.role).collect();
+    roles.sort();
+    roles.dedup();
+
+    for role in roles {
+        let members: Vec<&Member> = team
+            .members
+            .iter()
+            .filter(|member| member.role == *role)
+            .collect();
+
+        if members.len() > config.composition.slots(role) {
+            violations.push(Violation::RoleOverLimit {
+                team: name(),
+                role: role.clone(),
+                count: members.len(),
+            });
+        }
+
+        let lows: Vec<&&Member> = members
+            .iter()
+            .filter(|member| member.rank < config.limiter_max)
+            .collect();
+
+        if config.rank_limiter2
+            && !result.rank_limiter_relaxed
+            && lows.len() > 1
+            && lows.iter().any(|member| !is_anchor(players, member))
+        {
+            violations.push(Violation::RankLimit {
+                team: name(),
+                role: role.clone(),
+            });
+        }
+
+        if !config.duplicate_roles2 || result.duplicate_roles_relaxed {
+            continue;
+        }
+
+        for (index, member) in members.iter().enumerate() {
+            let sub_role = member.sub_role();
+            let duplicate = members[index + 1..].iter().find(|mate| {
+                sub_role.is_specialized()
+                    && mate.sub_role() == sub_role
+                    && !(is_anchor(players, member) && is_anchor(players, mate))
+            });
+
+            if duplicate.is_some() {
+                violations.push(Violation::DuplicateSubRole {
+                    team: name(),
+                    role: role.clone(),
+                    sub_role,
+                });
+            }
+        }
+    }
+}
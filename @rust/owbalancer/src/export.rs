@@ -0,0 +1,313 @@
+use crate::matchmaking::BalancerResult;
+use crate::rank_names::{RankError, RankSystem, RankSystemOption};
+use crate::roles::SimpleRole;
+use crate::teams::{Member, Team, Teams};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportFormat {
+    Csv,
+    Markdown,
+    Discord,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// Adds rank names next to ratings when set.
+    #[serde(default)]
+    pub rank_system: Option<RankSystemOption>,
+}
+
+/// Participant import formats of bracket platforms.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Platform {
+    /// `name,seed,misc` with the roster in `misc`.
+    Challonge,
+    /// Team name and seed followed by one column per player.
+    StartGg,
+    Json,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Participant {
+    pub name: String,
+    pub seed: usize,
+    pub average_rating: i32,
+    pub roster: Vec<RosterEntry>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RosterEntry {
+    pub name: String,
+    pub role: SimpleRole,
+    pub rank: i32,
+}
+
+pub struct Exporter {
+    format: ExportFormat,
+    ranks: Option<RankSystem>,
+}
+
+impl Exporter {
+    pub fn new(options: &ExportOptions) -> Result<Exporter, RankError> {
+        let ranks = match &options.rank_system {
+            Some(system) => Some(system.system()?),
+            None => None,
+        };
+
+        Ok(Exporter {
+            format: options.format,
+            ranks,
+        })
+    }
+
+    pub fn export(&self, result: &BalancerResult) -> String {
+        match self.format {
+            ExportFormat::Csv => self.csv(result),
+            ExportFormat::Markdown => self.markdown(result),
+            ExportFormat::Discord => self.discord(result),
+        }
+    }
+
+    fn role_emoji(role: &SimpleRole) -> &'static str {
+        match role.as_str() {
+            "tank" => "🛡️",
+            "dps" => "⚔️",
+            "support" => "💉",
+            _ => "▪️",
+        }
+    }
+
+    fn rank(&self, rank: i32) -> String {
+        match self.ranks.as_ref().and_then(|system| system.name(rank)) {
+            Some(name) => format!("{} ({})", rank, name),
+            None => rank.to_string(),
+        }
+    }
+
+    /// Text with Markdown syntax escaped, so names show up as typed.
+    fn escape_markdown(text: &str) -> String {
+        let mut escaped = String::with_capacity(text.len());
+
+        for character in text.chars() {
+            if "\\`*_[]<>|~".contains(character) {
+                escaped.push('\\');
+            }
+
+            escaped.push(character);
+        }
+
+        escaped
+    }
+
+    /// Members grouped by role, strongest first.
+    fn members(team: &Team) -> Vec<&Member> {
+        let mut members: Vec<&Member> = team.members.iter().collect();
+        members.sort_by(|a, b| a.role.cmp(&b.role).then(b.rank.cmp(&a.rank)));
+        members
+    }
+
+    /// One row per member, the result dispersion is repeated on every row.
+    fn csv(&self, result: &BalancerResult) -> String {
+        let mut writer = csv::Writer::from_writer(Vec::default());
+        let mut header = vec![
+            "team",
+            "team_average",
+            "dispersion",
+            "player",
+            "role",
+            "rank",
+        ];
+
+        if self.ranks.is_some() {
+            header.push("rank_name");
+        }
+
+        writer.write_record(&header).unwrap();
+
+        let dispersion = result.dispersion.to_string();
+
+        for team in &result.teams.0 {
+            let average = format!("{:.0}", team.avg_sr);
+
+            for member in Exporter::members(team) {
+                let mut record = vec![
+                    team.name.clone(),
+                    average.clone(),
+                    dispersion.clone(),
+                    member.name.clone(),
+                    member.role.get_string(),
+                    member.rank.to_string(),
+                ];
+
+                if let Some(system) = &self.ranks {
+                    record.push(
+                        system
+                            .name(member.rank)
+                            .map_or_else(String::new, |name| name.to_string()),
+                    );
+                }
+
+                writer.write_record(&record).unwrap();
+            }
+        }
+
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+
+    fn markdown(&self, result: &BalancerResult) -> String {
+        let mut output = String::new();
+
+        for team in &result.teams.0 {
+            writeln!(
+                output,
+                "### {} (average {:.0})",
+                Exporter::escape_markdown(&team.name),
+                team.avg_sr
+            )
+            .unwrap();
+            writeln!(output).unwrap();
+            writeln!(output, "| Player | Role | Rank |").unwrap();
+            writeln!(output, "| --- | --- | ---: |").unwrap();
+
+            for member in Exporter::members(team) {
+                writeln!(
+                    output,
+                    "| {} | {} | {} |",
+                    Exporter::escape_markdown(&member.name),
+                    member.role.as_str(),
+                    self.rank(member.rank)
+                )
+                .unwrap();
+            }
+
+            writeln!(output).unwrap();
+        }
+
+        writeln!(output, "**Dispersion:** {}", result.dispersion).unwrap();
+        output
+    }
+
+    fn discord(&self, result: &BalancerResult) -> String {
+        let mut output = String::new();
+
+        for team in &result.teams.0 {
+            writeln!(
+                output,
+                "**{}** · average {:.0}",
+                Exporter::escape_markdown(&team.name),
+                team.avg_sr
+            )
+            .unwrap();
+            writeln!(output, "```").unwrap();
+
+            for member in Exporter::members(team) {
+                writeln!(
+                    output,
+                    "{} {:<24} {}",
+                    Exporter::role_emoji(&member.role),
+                    member.name.replace('`', "'"),
+                    self.rank(member.rank)
+                )
+                .unwrap();
+            }
+
+            writeln!(output, "```").unwrap();
+        }
+
+        writeln!(output, "Dispersion: {}", result.dispersion).unwrap();
+        output
+    }
+}
+
+impl Participant {
+    /// Teams seeded by average rating, seed 1 is the strongest team.
+    pub fn from_teams(teams: &Teams) -> Vec<Participant> {
+        teams
+            .seeded()
+            .into_iter()
+            .enumerate()
+            .map(|(index, team)| Participant {
+                name: team.name.clone(),
+                seed: index + 1,
+                average_rating: team.avg_sr.round() as i32,
+                roster: Exporter::members(team)
+                    .into_iter()
+                    .map(|member| RosterEntry {
+                        name: member.name.clone(),
+                        role: member.role.clone(),
+                        rank: member.rank,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+impl Platform {
+    pub fn export(&self, teams: &Teams) -> String {
+        let participants = Participant::from_teams(teams);
+
+        match self {
+            Platform::Challonge => Platform::csv(
+                vec!["name", "seed", "misc"],
+                participants.iter().map(|participant| {
+                    let roster: Vec<&str> = participant
+                        .roster
+                        .iter()
+                        .map(|entry| entry.name.as_str())
+                        .collect();
+
+                    vec![
+                        participant.name.clone(),
+                        participant.seed.to_string(),
+                        roster.join(", "),
+                    ]
+                }),
+            ),
+            Platform::StartGg => {
+                let players = participants
+                    .iter()
+                    .map(|participant| participant.roster.len())
+                    .max()
+                    .unwrap_or(0);
+                let mut header = vec![String::from("Team Name"), String::from("Seed")];
+                header.extend((1..=players).map(|index| format!("Player {}", index)));
+
+                Platform::csv(
+                    header,
+                    participants.iter().map(|participant| {
+                        let mut record =
+                            vec![participant.name.clone(), participant.seed.to_string()];
+                        record.extend(participant.roster.iter().map(|entry| entry.name.clone()));
+                        record
+                    }),
+                )
+            }
+            Platform::Json => serde_json::to_string_pretty(&participants).unwrap(),
+        }
+    }
+
+    fn csv<H, R>(header: Vec<H>, records: impl Iterator<Item = Vec<R>>) -> String
+    where
+        H: AsRef<[u8]>,
+        R: AsRef<[u8]>,
+    {
+        let mut writer = csv::WriterBuilder::new()
+            .flexible(true)
+            .from_writer(Vec::default());
+        writer.write_record(header).unwrap();
+
+        for record in records {
+            writer.write_record(record).unwrap();
+        }
+
+        String::from_utf8(writer.into_inner().unwrap()).unwrap()
+    }
+}
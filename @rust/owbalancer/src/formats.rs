@@ -0,0 +1,212 @@
+use crate::matchmaking::BalancerResult;
+use crate::players::Players;
+use crate::roles::{Role, SimpleRole, SubRole};
+use crate::teams::Teams;
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version of the archived formats. Data without a version is version 1, the shape
+/// used before formats were versioned: roles were `{"Tank": [rank, primary, secondary]}`
+/// and teams had no `rawAvgSr` or `deviation`.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Archived objects the UI stores and sends back.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum Format {
+    Players,
+    Teams,
+    Result,
+    /// Result list the balance entry points return.
+    Results,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct Versioned<T> {
+    pub version: u32,
+    pub data: T,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum FormatError {
+    UnsupportedVersion { version: u64 },
+    Invalid { message: String },
+}
+
+impl<T> Versioned<T> {
+    pub fn new(data: T) -> Versioned<T> {
+        Versioned {
+            version: FORMAT_VERSION,
+            data,
+        }
+    }
+}
+
+impl Format {
+    /// Data of any known version in the current shape, without the version wrapper.
+    /// Migrations leave data that already has the newer shape untouched.
+    pub fn migrate(self, value: Value) -> Result<Value, FormatError> {
+        let (mut version, mut data) = Format::unwrap(value)?;
+
+        if version == 0 || version > FORMAT_VERSION as u64 {
+            return Err(FormatError::UnsupportedVersion { version });
+        }
+
+        while version < FORMAT_VERSION as u64 {
+            data = match version {
+                1 => self.v1_to_v2(data)?,
+                _ => unreachable!(),
+            };
+            version += 1;
+        }
+
+        Ok(data)
+    }
+
+    pub fn load<T: DeserializeOwned>(self, value: Value) -> Result<T, FormatError> {
+        let data = self.migrate(value)?;
+
+        serde_json::from_value(data).map_err(|error| FormatError::invalid(error.to_string()))
+    }
+
+    /// JSON Schema of the versioned format.
+    pub fn schema(self) -> RootSchema {
+        match self {
+            Format::Players => schema_for!(Versioned<Players>),
+            Format::Teams => schema_for!(Versioned<Teams>),
+            Format::Result => schema_for!(Versioned<BalancerResult>),
+            Format::Results => schema_for!(Versioned<Vec<BalancerResult>>),
+        }
+    }
+
+    fn unwrap(value: Value) -> Result<(u64, Value), FormatError> {
+        match value {
+            Value::Object(mut map) if map.contains_key("version") && map.contains_key("data") => {
+                let version = map["version"]
+                    .as_u64()
+                    .ok_or_else(|| FormatError::invalid("version is not a number"))?;
+
+                Ok((version, map.remove("data").unwrap()))
+            }
+            value => Ok((1, value)),
+        }
+    }
+
+    fn v1_to_v2(self, mut data: Value) -> Result<Value, FormatError> {
+        match self {
+            Format::Players => {}
+            Format::Teams => Format::teams_v1_to_v2(&mut data)?,
+            Format::Result => Format::result_v1_to_v2(&mut data)?,
+            Format::Results => {
+                let results = data
+                    .as_array_mut()
+                    .ok_or_else(|| FormatError::invalid("results is not an array"))?;
+
+                for result in results {
+                    Format::result_v1_to_v2(result)?;
+                }
+            }
+        }
+
+        Ok(data)
+    }
+
+    fn result_v1_to_v2(result: &mut Value) -> Result<(), FormatError> {
+        let result = result
+            .as_object_mut()
+            .ok_or_else(|| FormatError::invalid("result is not an object"))?;
+
+        if let Some(teams) = result.get_mut("teams") {
+            Format::teams_v1_to_v2(teams)?;
+        }
+
+        let candidates = result
+            .get_mut("leftovers")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+
+        for candidate in candidates {
+            let roles = candidate
+                .get_mut("roles")
+                .and_then(Value::as_array_mut)
+                .into_iter()
+                .flatten();
+
+            for role in roles {
+                *role = Format::role_v1_to_v2(role.take())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills in the plain average of member ranks.
+    fn teams_v1_to_v2(teams: &mut Value) -> Result<(), FormatError> {
+        let teams = teams
+            .as_array_mut()
+            .ok_or_else(|| FormatError::invalid("teams is not an array"))?;
+
+        for team in teams.iter_mut().filter_map(Value::as_object_mut) {
+            if team.contains_key("rawAvgSr") {
+                continue;
+            }
+
+            let ranks: Vec<f64> = team
+                .get("members")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|member| member.get("rank").and_then(Value::as_f64))
+                .collect();
+            let average = if ranks.is_empty() {
+                0.0
+            } else {
+                ranks.iter().sum::<f64>() / ranks.len() as f64
+            };
+
+            team.insert(String::from("rawAvgSr"), Value::from(average));
+        }
+
+        Ok(())
+    }
+
+    /// `{"Tank": [rank, primary, secondary]}` to `{"role": "tank", "rank": .., "subRole": ..}`.
+    fn role_v1_to_v2(role: Value) -> Result<Value, FormatError> {
+        let (name, values) = match &role {
+            Value::Object(map) if map.len() == 1 && !map.contains_key("role") => {
+                map.iter().next().unwrap()
+            }
+            _ => return Ok(role),
+        };
+        let values = values.as_array().map(Vec::as_slice).unwrap_or_default();
+
+        let (rank, primary, secondary) = match values {
+            [rank, primary, secondary] => (rank.as_i64(), primary.as_bool(), secondary.as_bool()),
+            _ => (None, None, None),
+        };
+
+        match (rank, primary, secondary) {
+            (Some(rank), Some(primary), Some(secondary)) => {
+                let simple = SimpleRole::new(&name.to_lowercase());
+                let sub_role = SubRole::new(&simple, primary, secondary);
+                let role = Role::new(simple, rank as i32, sub_role);
+
+                serde_json::to_value(role).map_err(|error| FormatError::invalid(error.to_string()))
+            }
+            _ => Err(FormatError::invalid(format!("malformed role {}", role))),
+        }
+    }
+}
+
+impl FormatError {
+    fn invalid(message: impl Into<String>) -> FormatError {
+        FormatError::Invalid {
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use crate::players::{ClassType, Classes, Identity, Player, Players, Stats};
+use crate::roles::{Composition, SimpleRole};
+use indexmap::IndexMap;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use uuid::{Builder, Variant, Version};
+
+/// Ranks of generated roles, always within `min` and `max`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RankDistribution {
+    Uniform {
+        min: i32,
+        max: i32,
+    },
+    Normal {
+        mean: f64,
+        deviation: f64,
+        min: i32,
+        max: i32,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleShare {
+    pub role: SimpleRole,
+    /// Relative share of players preferring the role.
+    pub share: f64,
+}
+
+/// Random lobbies for benchmarks and tests. The same settings and seed give the same lobby.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LobbyGenerator {
+    pub size: usize,
+    /// Every role of the lobby, players get their preferred role by share.
+    pub roles: Vec<RoleShare>,
+    pub ranks: RankDistribution,
+    pub captain_share: f64,
+    pub squire_share: f64,
+    /// Players with every role active, flagged as full flex.
+    pub flex_share: f64,
+    /// Chance of the next role by priority being active as well, the UI flips a coin.
+    pub off_role_chance: f64,
+    pub seed: Option<u64>,
+}
+
+impl Default for RankDistribution {
+    fn default() -> Self {
+        RankDistribution::Normal {
+            mean: 1250.0,
+            deviation: 350.0,
+            min: 100,
+            max: 2000,
+        }
+    }
+}
+
+impl RankDistribution {
+    pub fn sample(&self, rng: &mut impl Rng) -> i32 {
+        match *self {
+            RankDistribution::Uniform { min, max } => rng.gen_range(min, max.max(min) + 1),
+            RankDistribution::Normal {
+                mean,
+                deviation,
+                min,
+                max,
+            } => {
+                // Box-Muller, `1 - gen` keeps the logarithm finite
+                let radius = (-2.0 * (1.0 - rng.gen::<f64>()).ln()).sqrt();
+                let angle = 2.0 * PI * rng.gen::<f64>();
+                let rank = mean + deviation * radius * angle.cos();
+
+                (rank.round() as i32).max(min).min(max)
+            }
+        }
+    }
+}
+
+impl Default for LobbyGenerator {
+    fn default() -> Self {
+        LobbyGenerator {
+            size: 60,
+            roles: Composition::default()
+                .0
+                .into_iter()
+                .map(|slots| RoleShare {
+                    role: slots.role,
+                    share: slots.slots as f64,
+                })
+                .collect(),
+            ranks: RankDistribution::default(),
+            captain_share: 0.2,
+            squire_share: 0.2,
+            flex_share: 0.1,
+            off_role_chance: 0.5,
+            seed: None,
+        }
+    }
+}
+
+impl LobbyGenerator {
+    pub fn generate(&self) -> Players {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let captains = self.count(self.captain_share);
+        let squires = self.count(self.squire_share).min(self.size - captains);
+        let mut players = IndexMap::new();
+
+        for index in 0..self.size {
+            let is_captain = index < captains;
+            let is_squire = !is_captain && index < captains + squires;
+            let player = self.player(index, is_captain, is_squire, &mut rng);
+
+            players.insert(player.identity.uuid.clone(), player);
+        }
+
+        Players(players)
+    }
+
+    fn count(&self, share: f64) -> usize {
+        ((self.size as f64 * share.max(0.0)).round() as usize).min(self.size)
+    }
+
+    fn player(&self, index: usize, is_captain: bool, is_squire: bool, rng: &mut StdRng) -> Player {
+        let is_full_flex = rng.gen_bool(self.flex_share.clamp(0.0, 1.0));
+        let mut roles: Vec<&SimpleRole> = self.roles.iter().map(|share| &share.role).collect();
+        roles.shuffle(rng);
+
+        if let Ok(preferred) = self
+            .roles
+            .choose_weighted(rng, |share| share.share.max(0.0))
+        {
+            roles.retain(|&role| *role != preferred.role);
+            roles.insert(0, &preferred.role);
+        }
+
+        let mut classes = IndexMap::new();
+        let mut is_active = true;
+
+        for (priority, role) in roles.into_iter().enumerate() {
+            if priority > 0 && !is_full_flex {
+                is_active = is_active && rng.gen_bool(self.off_role_chance.clamp(0.0, 1.0));
+            }
+
+            let (primary, secondary) = *[(false, false), (true, false), (false, true)]
+                .choose(rng)
+                .unwrap();
+
+            classes.insert(
+                role.clone(),
+                ClassType {
+                    rank: if is_active { self.ranks.sample(rng) } else { 0 },
+                    priority: priority as i16 + 1,
+                    primary,
+                    secondary,
+                    is_active,
+                    deviation: None,
+                    volatility: None,
+                    uncertainty: 0,
+                },
+            );
+        }
+
+        let uuid = Builder::from_bytes(rng.gen())
+            .set_variant(Variant::RFC4122)
+            .set_version(Version::Random)
+            .build();
+
+        Player {
+            identity: Identity {
+                uuid: uuid.to_string(),
+                name: format!("Player {}", index + 1),
+                is_squire,
+                is_captain,
+                is_full_flex: Some(is_full_flex),
+            },
+            stats: Stats {
+                classes: Classes(classes),
+            },
+            created_at: String::new(),
+        }
+    }
+}
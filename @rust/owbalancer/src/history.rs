@@ -0,0 +1,86 @@
+use crate::teams::{Team, Teams};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How often each pair of players has been on the same team, weighted by recency: every
+/// event counts as its 1-based position, so the latest of ten events weighs ten times as
+/// much as the first one.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TeammateHistory {
+    pub pairs: HashMap<String, HashMap<String, u32>>,
+    pub events: u32,
+}
+
+/// Rosters oldest first.
+impl From<&[Teams]> for TeammateHistory {
+    fn from(rosters: &[Teams]) -> Self {
+        let mut history = TeammateHistory::default();
+
+        for teams in rosters {
+            history.add_teams(teams);
+        }
+
+        history
+    }
+}
+
+impl TeammateHistory {
+    /// Adds the rosters of an event newer than every event so far.
+    pub fn add_teams(&mut self, teams: &Teams) {
+        self.events += 1;
+
+        for team in &teams.0 {
+            self.add_team(team, self.events);
+        }
+    }
+
+    fn add_team(&mut self, team: &Team, weight: u32) {
+        for member in &team.members {
+            for mate in &team.members {
+                if member.uuid != mate.uuid {
+                    *self
+                        .pairs
+                        .entry(member.uuid.clone())
+                        .or_default()
+                        .entry(mate.uuid.clone())
+                        .or_insert(0) += weight;
+                }
+            }
+        }
+    }
+
+    /// Events `uuid` and `mate` played together, each weighted by its recency.
+    pub fn count(&self, uuid: &str, mate: &str) -> u32 {
+        self.pairs
+            .get(uuid)
+            .and_then(|mates| mates.get(mate))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Previous games `uuid` has played with the current members of `team`.
+    pub fn repeats_with(&self, team: &Team, uuid: &str) -> u32 {
+        team.members
+            .iter()
+            .filter(|member| member.uuid != uuid)
+            .map(|member| self.count(uuid, &member.uuid))
+            .sum()
+    }
+
+    /// Pairs of the team that already played together, weighted by how often and how recently.
+    pub fn team_repeats(&self, team: &Team) -> u32 {
+        let mut repeats = 0;
+
+        for (index, member) in team.members.iter().enumerate() {
+            for mate in &team.members[index + 1..] {
+                repeats += self.count(&member.uuid, &mate.uuid);
+            }
+        }
+
+        repeats
+    }
+
+    pub fn total_repeats(&self, teams: &Teams) -> u32 {
+        teams.0.iter().map(|team| self.team_repeats(team)).sum()
+    }
+}
@@ -0,0 +1,331 @@
+use crate::players::{ClassType, Classes, Identity, Player, Players, Stats};
+use crate::rank_names::{RankError, RankSystem, RankSystemOption};
+use crate::roles::SimpleRole;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Columns of a sign-up sheet, referenced by their header.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ColumnMapping {
+    pub name: String,
+    #[serde(default)]
+    pub captain: Option<String>,
+    #[serde(default)]
+    pub squire: Option<String>,
+    #[serde(default)]
+    pub full_flex: Option<String>,
+    pub roles: Vec<RoleColumns>,
+    #[serde(default = "ColumnMapping::default_delimiter")]
+    pub delimiter: char,
+    /// Used for rank cells holding names like `Diamond 3` instead of numbers.
+    #[serde(default)]
+    pub rank_system: RankSystemOption,
+}
+
+/// Role is active when its rank cell is not empty. Without a priority column
+/// roles are prioritized in mapping order, starting at 1.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleColumns {
+    pub role: SimpleRole,
+    pub rank: String,
+    #[serde(default)]
+    pub priority: Option<String>,
+    #[serde(default)]
+    pub primary: Option<String>,
+    #[serde(default)]
+    pub secondary: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ImportErrorKind {
+    MissingColumn,
+    /// Mapped header found more than once, so its column is ambiguous.
+    DuplicateColumn,
+    /// Only ASCII delimiters are supported.
+    InvalidDelimiter {
+        delimiter: char,
+    },
+    Malformed {
+        message: String,
+    },
+    RankSystem {
+        error: RankError,
+    },
+    EmptyName,
+    InvalidRank {
+        value: String,
+    },
+    InvalidPriority {
+        value: String,
+    },
+    InvalidFlag {
+        value: String,
+    },
+}
+
+/// `row` is 1-based and counts the header, 0 reports the whole sheet.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportError {
+    pub row: usize,
+    pub column: String,
+    pub error: ImportErrorKind,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ImportResult {
+    pub players: Players,
+    pub errors: Vec<ImportError>,
+}
+
+/// Header positions resolved from the mapping.
+struct Columns {
+    headers: HashMap<String, usize>,
+}
+
+impl ColumnMapping {
+    fn default_delimiter() -> char {
+        ','
+    }
+
+    fn columns(&self) -> Vec<&String> {
+        let mut columns = vec![&self.name];
+        columns.extend(self.captain.iter());
+        columns.extend(self.squire.iter());
+        columns.extend(self.full_flex.iter());
+
+        for role in &self.roles {
+            columns.push(&role.rank);
+            columns.extend(role.priority.iter());
+            columns.extend(role.primary.iter());
+            columns.extend(role.secondary.iter());
+        }
+
+        columns
+    }
+}
+
+impl Columns {
+    fn get<'a>(&self, record: &'a csv::StringRecord, column: &str) -> &'a str {
+        self.headers
+            .get(column)
+            .and_then(|&index| record.get(index))
+            .map_or("", |value| value.trim())
+    }
+
+    fn flag(
+        &self,
+        record: &csv::StringRecord,
+        column: &Option<String>,
+    ) -> Result<bool, ImportError> {
+        let column = match column {
+            Some(column) => column,
+            None => return Ok(false),
+        };
+        let value = self.get(record, column);
+
+        match value.to_lowercase().as_str() {
+            "" | "0" | "no" | "n" | "false" => Ok(false),
+            "1" | "yes" | "y" | "true" | "x" | "+" => Ok(true),
+            _ => Err(ImportError {
+                row: 0,
+                column: column.clone(),
+                error: ImportErrorKind::InvalidFlag {
+                    value: value.to_string(),
+                },
+            }),
+        }
+    }
+}
+
+impl ImportResult {
+    /// Parses sign-ups into players. Rows with errors are reported and skipped.
+    pub fn from_csv(data: &str, mapping: &ColumnMapping) -> ImportResult {
+        let mut result = ImportResult::default();
+
+        if !mapping.delimiter.is_ascii() {
+            result.errors.push(ImportError {
+                row: 0,
+                column: String::new(),
+                error: ImportErrorKind::InvalidDelimiter {
+                    delimiter: mapping.delimiter,
+                },
+            });
+            return result;
+        }
+
+        let mut reader = csv::ReaderBuilder::new()
+            .delimiter(mapping.delimiter as u8)
+            .flexible(true)
+            .from_reader(data.as_bytes());
+
+        let mut duplicates = Vec::default();
+        let headers = match reader.headers() {
+            Ok(headers) => {
+                let mut positions = HashMap::new();
+
+                for (index, header) in headers.iter().enumerate() {
+                    match positions.entry(header.trim().to_string()) {
+                        Entry::Occupied(entry) => duplicates.push(entry.key().clone()),
+                        Entry::Vacant(entry) => {
+                            entry.insert(index);
+                        }
+                    }
+                }
+
+                positions
+            }
+            Err(error) => {
+                result.errors.push(ImportError::malformed(1, error));
+                return result;
+            }
+        };
+        let columns = Columns { headers };
+        let system = match mapping.rank_system.system() {
+            Ok(system) => system,
+            Err(error) => {
+                result.errors.push(ImportError {
+                    row: 0,
+                    column: String::new(),
+                    error: ImportErrorKind::RankSystem { error },
+                });
+                return result;
+            }
+        };
+
+        for column in mapping.columns() {
+            if !columns.headers.contains_key(column) {
+                result.errors.push(ImportError {
+                    row: 0,
+                    column: column.clone(),
+                    error: ImportErrorKind::MissingColumn,
+                });
+            } else if duplicates.contains(column) {
+                result.errors.push(ImportError {
+                    row: 0,
+                    column: column.clone(),
+                    error: ImportErrorKind::DuplicateColumn,
+                });
+            }
+        }
+
+        if !result.errors.is_empty() {
+            return result;
+        }
+
+        for (index, record) in reader.records().enumerate() {
+            let row = index + 2;
+
+            let player = record
+                .map_err(|error| ImportError::malformed(row, error))
+                .and_then(|record| ImportResult::parse_player(&record, &columns, mapping, &system))
+                .map_err(|error| ImportError { row, ..error });
+
+            match player {
+                Ok(player) => {
+                    result
+                        .players
+                        .0
+                        .insert(player.identity.uuid.clone(), player);
+                }
+                Err(error) => result.errors.push(error),
+            }
+        }
+
+        result
+    }
+
+    fn parse_player(
+        record: &csv::StringRecord,
+        columns: &Columns,
+        mapping: &ColumnMapping,
+        system: &RankSystem,
+    ) -> Result<Player, ImportError> {
+        let name = columns.get(record, &mapping.name);
+
+        if name.is_empty() {
+            return Err(ImportError {
+                row: 0,
+                column: mapping.name.clone(),
+                error: ImportErrorKind::EmptyName,
+            });
+        }
+
+        let mut classes = IndexMap::new();
+
+        for (order, role) in mapping.roles.iter().enumerate() {
+            let rank = columns.get(record, &role.rank);
+            let is_active = !rank.is_empty();
+            let rank = if is_active {
+                system.parse(rank).map_err(|_| ImportError {
+                    row: 0,
+                    column: role.rank.clone(),
+                    error: ImportErrorKind::InvalidRank {
+                        value: rank.to_string(),
+                    },
+                })?
+            } else {
+                0
+            };
+
+            let priority = match &role.priority {
+                Some(column) if !columns.get(record, column).is_empty() => {
+                    let value = columns.get(record, column);
+                    value.parse::<i16>().map_err(|_| ImportError {
+                        row: 0,
+                        column: column.clone(),
+                        error: ImportErrorKind::InvalidPriority {
+                            value: value.to_string(),
+                        },
+                    })?
+                }
+                _ => order as i16 + 1,
+            };
+
+            classes.insert(
+                role.role.clone(),
+                ClassType {
+                    rank,
+                    priority,
+                    primary: columns.flag(record, &role.primary)?,
+                    secondary: columns.flag(record, &role.secondary)?,
+                    is_active,
+                    deviation: None,
+                    volatility: None,
+                    uncertainty: 0,
+                },
+            );
+        }
+
+        Ok(Player {
+            identity: Identity {
+                uuid: Uuid::new_v4().to_string(),
+                name: name.to_string(),
+                is_captain: columns.flag(record, &mapping.captain)?,
+                is_squire: columns.flag(record, &mapping.squire)?,
+                is_full_flex: Some(columns.flag(record, &mapping.full_flex)?),
+            },
+            stats: Stats {
+                classes: Classes(classes),
+            },
+            created_at: String::new(),
+        })
+    }
+}
+
+impl ImportError {
+    fn malformed(row: usize, error: csv::Error) -> ImportError {
+        ImportError {
+            row,
+            column: String::new(),
+            error: ImportErrorKind::Malformed {
+                message: error.to_string(),
+            },
+        }
+    }
+}
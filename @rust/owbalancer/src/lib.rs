@@ -1,12 +1,35 @@
+pub mod arena;
+pub mod checks;
+pub mod export;
+pub mod formats;
+pub mod generator;
+pub mod history;
+pub mod import;
 pub mod matchmaking;
+pub mod pairing;
 pub mod players;
+pub mod prediction;
+pub mod rank_names;
 pub mod rating_scaler;
+pub mod ratings;
 pub mod roles;
 pub mod teams;
 
+use export::{ExportOptions, Exporter, Platform};
+use formats::{Format, FormatError, Versioned};
+use generator::LobbyGenerator;
+use history::TeammateHistory;
+use import::{ColumnMapping, ImportResult};
 use matchmaking::{BalancerResult, Matchmaking};
-use players::Players;
+use pairing::{PairingOptions, Schedule};
+use players::{Players, RankIssue};
+use prediction::Predictor;
+use rank_names::RankSystemOption;
+use rating_scaler::{CurveIssue, RatingScaler};
+use ratings::{MatchResult, RatingModel};
+use roles::{Composition, SimpleRole};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use teams::Teams;
 use wasm_bindgen::prelude::*;
 
@@ -29,27 +52,253 @@ pub struct BezierPoint {
     pub control: Point,
 }
 
+/// Rating adjustment of a single role specialization. Bare point arrays are Bézier
+/// curves; other models are objects keyed by their name, e.g. `{ "percent": 5 }`.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Adjustment {
+    Bezier(Vec<BezierPoint>),
+    Linear { linear: Vec<Point> },
+    Offset { offset: i32 },
+    Percent { percent: f64 },
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SpecializationPoints {
-    pub any: Vec<BezierPoint>,
-    pub primary: Vec<BezierPoint>,
-    pub secondary: Vec<BezierPoint>,
+    pub any: Adjustment,
+    pub primary: Adjustment,
+    pub secondary: Adjustment,
+}
+
+/// Rating range the adjustment points are normalized against. Point `x` is a share of
+/// `top_rating`, point `y` is a share of `max_adjustment` percent.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct RatingScale {
+    pub top_rating: f64,
+    pub max_adjustment: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct AdjustSr {
-    pub dps: SpecializationPoints,
-    pub tank: SpecializationPoints,
-    pub support: SpecializationPoints,
     pub is_enabled: bool,
+    #[serde(default)]
+    pub scale: RatingScale,
+    /// Adjustments keyed by role id, next to the options above.
+    #[serde(flatten)]
+    pub roles: HashMap<SimpleRole, SpecializationPoints>,
+}
+
+impl Default for RatingScale {
+    fn default() -> Self {
+        RatingScale {
+            top_rating: 2000.0,
+            max_adjustment: 50.0,
+        }
+    }
+}
+
+impl RatingScale {
+    pub fn contains(&self, rating: i32) -> bool {
+        rating >= 0 && rating as f64 <= self.top_rating
+    }
+}
+
+impl SpecializationPoints {
+    pub fn get(&self, specialization: &str) -> Option<&Adjustment> {
+        match specialization {
+            "any" => Some(&self.any),
+            "primary" => Some(&self.primary),
+            "secondary" => Some(&self.secondary),
+            _ => None,
+        }
+    }
+}
+
+impl AdjustSr {
+    pub fn get(&self, role: &SimpleRole) -> Option<&SpecializationPoints> {
+        self.roles.get(role)
+    }
+
+    /// Role ids in a stable order.
+    pub fn role_ids(&self) -> Vec<&SimpleRole> {
+        let mut roles: Vec<&SimpleRole> = self.roles.keys().collect();
+        roles.sort();
+        roles
+    }
+}
+
+/// Optional lobby settings; every missing field falls back to the Overwatch defaults.
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LobbyOptions {
+    pub composition: Composition,
+    /// Applied to the rank of every role but the preferred one, e.g. `{ "percent": -10 }`.
+    pub off_role_penalty: Option<Adjustment>,
+    /// Rating points a result is penalized for each priority step a player is placed
+    /// below their preferred role, 0 ignores preferences when ranking results.
+    pub preference_weight: u32,
+    pub predictor: Predictor,
+    /// Team strength takes this many rank deviations off each rank, 0 balances on the plain
+    /// rank. Displayed ranks are not lowered.
+    pub conservative_factor: f64,
+    /// Rosters of earlier events oldest first, players are kept apart from their former
+    /// teammates and more so from recent ones, see `TeammateHistory`.
+    pub history: Vec<Teams>,
+    /// Makes balancing repeatable, every try of a lobby uses the next seed.
+    pub seed: Option<u64>,
+    /// Every result is checked against the balancer invariants, broken ones are
+    /// returned as `violations`.
+    pub check_results: bool,
+}
+
+/// Input the balancer refuses to run on.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum BalanceError {
+    /// Enabled rating adjustment with broken curves, see `RatingScaler::validate`.
+    InvalidCurves { issues: Vec<CurveIssue> },
+    /// Ranks outside the scale of an enabled rating adjustment, see `Players::out_of_scale`.
+    OutOfScale { issues: Vec<RankIssue> },
+    /// Teams of a half balanced lobby that cannot be loaded.
+    Format { error: FormatError },
+    /// Input that does not have the expected shape.
+    Invalid { message: String },
+}
+
+impl From<BalanceError> for JsValue {
+    fn from(error: BalanceError) -> Self {
+        JsValue::from_serde(&error).unwrap()
+    }
 }
 
+impl BalanceError {
+    fn invalid(error: impl ToString) -> BalanceError {
+        BalanceError::Invalid {
+            message: error.to_string(),
+        }
+    }
+}
+
+impl AdjustSr {
+    /// Rating adjustment as edited, curves are not validated.
+    pub fn parse(adjust_sr: &JsValue) -> Result<AdjustSr, BalanceError> {
+        adjust_sr.into_serde().map_err(BalanceError::invalid)
+    }
+
+    /// Rating adjustment of the balance input, enabled adjustments have to be valid.
+    pub fn from_js(adjust_sr: &JsValue) -> Result<AdjustSr, BalanceError> {
+        let adjust = AdjustSr::parse(adjust_sr)?;
+
+        if adjust.is_enabled {
+            let issues = RatingScaler::validate(&adjust);
+
+            if !issues.is_empty() {
+                return Err(BalanceError::InvalidCurves { issues });
+            }
+        }
+
+        Ok(adjust)
+    }
+
+    /// Curves are normalized against the scale, ranks outside of it would be scaled wrong.
+    pub fn check_ranks(&self, players: &Players) -> Result<(), BalanceError> {
+        if !self.is_enabled {
+            return Ok(());
+        }
+
+        let issues = players.out_of_scale(&self.scale);
+
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(BalanceError::OutOfScale { issues })
+        }
+    }
+}
+
+impl RankSystemOption {
+    pub fn from_js(system: &JsValue) -> RankSystemOption {
+        if system.is_undefined() || system.is_null() {
+            return RankSystemOption::default();
+        }
+
+        system.into_serde().unwrap()
+    }
+}
+
+impl LobbyOptions {
+    pub fn from_js(options: &JsValue) -> Result<LobbyOptions, BalanceError> {
+        if options.is_undefined() || options.is_null() {
+            return Ok(LobbyOptions::default());
+        }
+
+        options.into_serde().map_err(BalanceError::invalid)
+    }
+
+    /// Applies the rating adjustment and the off-role penalty to player ranks. The
+    /// penalty curve is validated like the adjustment curves.
+    pub fn adjust_players(
+        &self,
+        players: &mut Players,
+        adjust: AdjustSr,
+    ) -> Result<(), BalanceError> {
+        let scale = adjust.scale;
+
+        if let Some(penalty) = &self.off_role_penalty {
+            let issues = RatingScaler::validate_penalty(penalty, &scale);
+
+            if !issues.is_empty() {
+                return Err(BalanceError::InvalidCurves { issues });
+            }
+        }
+
+        players.adjust_sr(adjust);
+
+        if let Some(penalty) = &self.off_role_penalty {
+            players.penalize_off_roles(penalty, &scale);
+        }
+
+        if self.conservative_factor > 0.0 {
+            players.apply_conservative(self.conservative_factor);
+        }
+
+        Ok(())
+    }
+
+    pub fn history(&self) -> TeammateHistory {
+        TeammateHistory::from(self.history.as_slice())
+    }
+
+    /// Seed of the `attempt`-th try.
+    pub fn seed(&self, attempt: u64) -> Option<u64> {
+        self.seed.map(|seed| seed.wrapping_add(attempt))
+    }
+
+    /// Settings of the `attempt`-th try.
+    pub fn apply(&self, matchmaking: &mut Matchmaking, attempt: u64) {
+        matchmaking.set_composition(self.composition.clone());
+        matchmaking.set_preference_weight(self.preference_weight);
+        matchmaking.set_predictor(self.predictor);
+        matchmaking.set_checked(self.check_results);
+
+        if let Some(seed) = self.seed(attempt) {
+            matchmaking.set_seed(seed);
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(module = "/logger.js")]
 extern "C" {
     pub fn wasm_log(message: String);
 }
 
+/// Progress goes to the worker, native builds such as benchmarks drop it.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn wasm_log(_message: String) {}
+
 #[wasm_bindgen(start)]
 pub fn main_js() -> Result<(), JsValue> {
     // #[cfg(debug_assertions)]
@@ -58,6 +307,7 @@ pub fn main_js() -> Result<(), JsValue> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn run_matchmaking(
     player_data: &JsValue,
     tolerance: u32,
@@ -66,13 +316,19 @@ pub fn run_matchmaking(
     adjust_sr: &JsValue,
     disable_type: String,
     dispersion_minimizer: bool,
-) -> BalancerResult {
+    options: &LobbyOptions,
+    history: &TeammateHistory,
+    attempt: u64,
+) -> Result<BalancerResult, BalanceError> {
     let mut players: Players = player_data.into_serde().unwrap();
-    let adjust: AdjustSr = adjust_sr.into_serde().unwrap();
-    players.adjust_sr(adjust);
+    let adjust = AdjustSr::from_js(adjust_sr)?;
+    adjust.check_ranks(&players)?;
+    options.adjust_players(&mut players, adjust)?;
 
     let mut matchmaking = Matchmaking::new(&players, tolerance, rank_limiter, duplicate_roles);
     matchmaking.set_disable_type(disable_type.clone());
+    options.apply(&mut matchmaking, attempt);
+    matchmaking.set_history(history.clone());
 
     if dispersion_minimizer {
         matchmaking.enable_dispersion_minimizer();
@@ -80,10 +336,13 @@ pub fn run_matchmaking(
 
     matchmaking.balance_players();
 
-    matchmaking.result()
+    Ok(matchmaking.result())
 }
 
+/// Results of the best tries. Like every balance entry point it returns a `Versioned`
+/// list, so saved results load without a migration.
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn balance(
     player_data: &JsValue,
     tolerance: u32,
@@ -93,11 +352,16 @@ pub fn balance(
     disable_type: String,
     dispersion_minimizer: bool,
     tries_count: u32,
-) -> JsValue {
+    results_count: u32,
+    min_difference: u32,
+    lobby_options: &JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = LobbyOptions::from_js(lobby_options)?;
+    let history = options.history();
     let mut results = Vec::default();
     let mut success_flag = false;
 
-    for _ in 0..tries_count {
+    for attempt in 0..tries_count {
         let result = run_matchmaking(
             player_data,
             tolerance,
@@ -106,13 +370,16 @@ pub fn balance(
             adjust_sr,
             disable_type.clone(),
             dispersion_minimizer,
-        );
+            &options,
+            &history,
+            attempt as u64,
+        )?;
 
-        if result.leftovers.0.len() == 0 && !success_flag {
+        if result.leftovers.0.is_empty() && !success_flag {
             success_flag = true;
         }
 
-        if success_flag && result.leftovers.0.len() > 0 {
+        if success_flag && !result.leftovers.0.is_empty() {
             continue;
         }
 
@@ -120,10 +387,65 @@ pub fn balance(
     }
 
     if success_flag {
-        results.retain(|result| result.leftovers.0.len() == 0);
+        results.retain(|result| result.leftovers.0.is_empty());
     }
 
-    JsValue::from_serde(&results).unwrap()
+    let results =
+        BalancerResult::select_diverse(results, results_count as usize, min_difference as usize);
+
+    Ok(JsValue::from_serde(&Versioned::new(results)).unwrap())
+}
+
+/// Balances `rounds` rounds from the same lobby, each avoiding teammates of the
+/// previous rounds. Returns one result per round, `withinTolerance` tells whether
+/// the round kept dispersion within `tolerance`.
+#[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
+pub fn balance_mixer(
+    player_data: &JsValue,
+    tolerance: u32,
+    rank_limiter: bool,
+    duplicate_roles: bool,
+    adjust_sr: &JsValue,
+    disable_type: String,
+    dispersion_minimizer: bool,
+    tries_count: u32,
+    rounds: u32,
+    lobby_options: &JsValue,
+) -> Result<JsValue, JsValue> {
+    let options = LobbyOptions::from_js(lobby_options)?;
+    let mut history = options.history();
+    let mut results = Vec::default();
+
+    if tries_count == 0 {
+        return Err(BalanceError::invalid("a mixer round needs at least one try").into());
+    }
+
+    for round in 0..rounds {
+        let candidates = (0..tries_count)
+            .map(|attempt| {
+                run_matchmaking(
+                    player_data,
+                    tolerance,
+                    rank_limiter,
+                    duplicate_roles,
+                    adjust_sr,
+                    disable_type.clone(),
+                    dispersion_minimizer,
+                    &options,
+                    &history,
+                    round as u64 * tries_count as u64 + attempt as u64,
+                )
+            })
+            .collect::<Result<_, _>>()?;
+
+        let round = BalancerResult::select_round(candidates, tolerance, &history)
+            .expect("every round has at least one try");
+        history.add_teams(&round.teams);
+        results.push(round);
+    }
+
+    Ok(JsValue::from_serde(&Versioned::new(results)).unwrap())
 }
 
 #[wasm_bindgen]
@@ -133,21 +455,26 @@ pub fn balance_half(
     rank_limiter: bool,
     duplicate_roles: bool,
     adjust_sr: &JsValue,
-) -> JsValue {
+    lobby_options: &JsValue,
+) -> Result<JsValue, JsValue> {
     let mut players: Players = player_data.into_serde().unwrap();
-    let adjust: AdjustSr = adjust_sr.into_serde().unwrap();
-    players.adjust_sr(adjust);
+    let adjust = AdjustSr::from_js(adjust_sr)?;
+    adjust.check_ranks(&players)?;
+    let options = LobbyOptions::from_js(lobby_options)?;
+    options.adjust_players(&mut players, adjust)?;
 
     let mut matchmaking = Matchmaking::new(&players, tolerance, rank_limiter, duplicate_roles);
+    options.apply(&mut matchmaking, 0);
+    matchmaking.set_history(options.history());
     matchmaking.balance_half();
 
-    let mut results = Vec::default();
-    results.push(matchmaking.result());
+    let results = vec![matchmaking.result()];
 
-    JsValue::from_serde(&results).unwrap()
+    Ok(JsValue::from_serde(&Versioned::new(results)).unwrap())
 }
 
 #[wasm_bindgen]
+#[allow(clippy::too_many_arguments)]
 pub fn balance_final(
     player_data: &JsValue,
     tolerance: u32,
@@ -156,21 +483,162 @@ pub fn balance_final(
     reserve_data: &JsValue,
     teams_data: &JsValue,
     adjust_sr: &JsValue,
-) -> JsValue {
+    lobby_options: &JsValue,
+) -> Result<JsValue, JsValue> {
     let mut players: Players = player_data.into_serde().unwrap();
-    let adjust: AdjustSr = adjust_sr.into_serde().unwrap();
-    players.adjust_sr(adjust);
+    let adjust = AdjustSr::from_js(adjust_sr)?;
+    adjust.check_ranks(&players)?;
+    let options = LobbyOptions::from_js(lobby_options)?;
+    options.adjust_players(&mut players, adjust)?;
 
-    let teams: Teams = teams_data.into_serde().unwrap();
+    let teams: Teams = teams_data
+        .into_serde()
+        .map_err(|error| FormatError::Invalid {
+            message: error.to_string(),
+        })
+        .and_then(|data| Format::Teams.load(data))
+        .map_err(|error| BalanceError::Format { error })?;
     let reserve: ReserveData = reserve_data.into_serde().unwrap();
 
     let mut matchmaking = Matchmaking::new(&players, tolerance, rank_limiter, duplicate_roles);
+    options.apply(&mut matchmaking, 0);
+    matchmaking.set_history(options.history());
     matchmaking.add_reserve(reserve.0);
     matchmaking.add_teams(teams);
     matchmaking.balance_remaining();
 
-    let mut results = Vec::default();
-    results.push(matchmaking.result());
+    let results = vec![matchmaking.result()];
+
+    Ok(JsValue::from_serde(&Versioned::new(results)).unwrap())
+}
+
+#[wasm_bindgen]
+pub fn pair_teams(teams_data: &JsValue, pairing_options: &JsValue) -> JsValue {
+    let teams: Teams = teams_data.into_serde().unwrap();
+    let options: PairingOptions = pairing_options.into_serde().unwrap();
+    let schedule = Schedule::new(&teams, &options);
+
+    JsValue::from_serde(&schedule).unwrap()
+}
+
+/// Returns `player_data` with role ranks updated from the played matches, or a
+/// `RatingError` for an invalid rating model.
+#[wasm_bindgen]
+pub fn update_ratings(
+    player_data: &JsValue,
+    matches: &JsValue,
+    rating_model: &JsValue,
+) -> Result<JsValue, JsValue> {
+    let mut players: Players = player_data.into_serde().unwrap();
+    let matches: Vec<MatchResult> = matches.into_serde().unwrap();
+    let model: RatingModel = rating_model.into_serde().unwrap();
+
+    match model.update(&mut players, &matches) {
+        Ok(()) => Ok(JsValue::from_serde(&players).unwrap()),
+        Err(error) => Err(JsValue::from_serde(&error).unwrap()),
+    }
+}
+
+/// Result as text in the requested format, or a `RankError` for an invalid rank system.
+#[wasm_bindgen]
+pub fn export_result(result_data: &JsValue, export_options: &JsValue) -> Result<String, JsValue> {
+    let result: BalancerResult = result_data.into_serde().unwrap();
+    let options: ExportOptions = export_options.into_serde().unwrap();
+
+    match Exporter::new(&options) {
+        Ok(exporter) => Ok(exporter.export(&result)),
+        Err(error) => Err(JsValue::from_serde(&error).unwrap()),
+    }
+}
+
+/// Participant list of a bracket platform, ready to be uploaded.
+#[wasm_bindgen]
+pub fn export_teams(teams_data: &JsValue, platform: &JsValue) -> String {
+    let teams: Teams = teams_data.into_serde().unwrap();
+    let platform: Platform = platform.into_serde().unwrap();
+
+    platform.export(&teams)
+}
+
+/// Archived players, teams or results of any known version in the current shape
+/// and version, fails with a `FormatError`.
+#[wasm_bindgen]
+pub fn migrate_format(format: &JsValue, data: &JsValue) -> Result<JsValue, JsValue> {
+    let migrated = format
+        .into_serde::<Format>()
+        .and_then(|format| Ok((format, data.into_serde()?)))
+        .map_err(|error| FormatError::Invalid {
+            message: error.to_string(),
+        })
+        .and_then(|(format, data)| format.migrate(data));
+
+    match migrated {
+        Ok(data) => Ok(JsValue::from_serde(&Versioned::new(data)).unwrap()),
+        Err(error) => Err(JsValue::from_serde(&error).unwrap()),
+    }
+}
+
+#[wasm_bindgen]
+pub fn format_schema(format: &JsValue) -> JsValue {
+    let format: Format = format.into_serde().unwrap();
+
+    JsValue::from_serde(&format.schema()).unwrap()
+}
+
+/// Random players, see `LobbyGenerator` for the settings.
+#[wasm_bindgen]
+pub fn generate_lobby(generator_options: &JsValue) -> JsValue {
+    let generator: LobbyGenerator = generator_options.into_serde().unwrap();
+
+    JsValue::from_serde(&generator.generate()).unwrap()
+}
+
+#[wasm_bindgen]
+pub fn import_players(csv_data: String, column_mapping: &JsValue) -> JsValue {
+    let mapping: ColumnMapping = column_mapping.into_serde().unwrap();
+    let result = ImportResult::from_csv(&csv_data, &mapping);
+
+    JsValue::from_serde(&result).unwrap()
+}
+
+/// Rating of a rank name such as `Diamond 3`, fails with a `RankError`.
+#[wasm_bindgen]
+pub fn parse_rank(rank: String, rank_system: &JsValue) -> Result<JsValue, JsValue> {
+    let result = RankSystemOption::from_js(rank_system)
+        .system()
+        .and_then(|system| system.parse(&rank));
+
+    match result {
+        Ok(rating) => Ok(JsValue::from_serde(&rating).unwrap()),
+        Err(error) => Err(JsValue::from_serde(&error).unwrap()),
+    }
+}
+
+#[wasm_bindgen]
+pub fn rank_name(rating: i32, rank_system: &JsValue) -> JsValue {
+    let name = RankSystemOption::from_js(rank_system)
+        .system()
+        .ok()
+        .and_then(|system| system.name(rating));
+
+    JsValue::from_serde(&name).unwrap()
+}
+
+/// Sampled curves and their issues, fails with a `BalanceError` for malformed input.
+#[wasm_bindgen]
+pub fn preview_adjust_sr(adjust_sr: &JsValue, step: u32) -> Result<JsValue, JsValue> {
+    let adjust = AdjustSr::parse(adjust_sr)?;
+    let preview = RatingScaler::preview(&adjust, step as i32);
+
+    Ok(JsValue::from_serde(&preview).unwrap())
+}
+
+/// Ranks outside the adjustment scale, fails with a `BalanceError` for malformed input.
+#[wasm_bindgen]
+pub fn validate_ranks(player_data: &JsValue, adjust_sr: &JsValue) -> Result<JsValue, JsValue> {
+    let players: Players = player_data.into_serde().map_err(BalanceError::invalid)?;
+    let adjust = AdjustSr::parse(adjust_sr)?;
+    let issues = players.out_of_scale(&adjust.scale);
 
-    JsValue::from_serde(&results).unwrap()
+    Ok(JsValue::from_serde(&issues).unwrap())
 }
@@ -1,7 +1,15 @@
+use crate::arena::{Arena, Reserve};
+use crate::checks::{check_result, Violation};
+use crate::history::TeammateHistory;
 use crate::players::{Candidate, Direction, PlayerPool, Players};
-use crate::roles::SimpleRole;
-use crate::teams::{Member, Team, Teams};
+use crate::prediction::{Predictions, Predictor};
+use crate::roles::{Composition, SimpleRole};
+use crate::roles::{Role, Roles};
+use crate::teams::{Member, SubRoleDuplicate, Team, Teams};
 use crate::wasm_log;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::cmp;
 use std::collections::HashMap;
@@ -19,7 +27,13 @@ pub struct Config {
     pub duplicate_roles: bool,
     pub duplicate_roles2: bool,
     pub dispersion_minimizer: bool,
-    pub roles_avg: HashMap<String, i32>,
+    pub roles_avg: HashMap<SimpleRole, i32>,
+    pub composition: Composition,
+    pub preference_weight: u32,
+    pub predictor: Predictor,
+    pub history: TeammateHistory,
+    #[serde(skip)]
+    pub arena: Arena,
 }
 
 pub struct Matchmaking<'a> {
@@ -28,16 +42,112 @@ pub struct Matchmaking<'a> {
     pool: PlayerPool,
     players: &'a Players,
     balanced: Vec<String>,
-    reserve_pool: PlayerPool,
+    reserve: Reserve,
     disable_type: String,
+    rng: StdRng,
+    checked: bool,
+    relaxed: Relaxations,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+/// Settings the caller asked for that balancing broke to fill teams.
+#[derive(Debug, Default, Clone, Copy)]
+struct Relaxations {
+    rank_limiter: bool,
+    duplicate_roles: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct BalancerResult {
     pub teams: Teams,
     pub leftovers: PlayerPool,
     pub dispersion: i32,
     pub anchors: i32,
+    pub occurrences: u32,
+    pub duplicates: Vec<SubRoleDuplicate>,
+    pub preferences: Preferences,
+    /// Rating points added to dispersion when ranking results, see `Config::preference_weight`.
+    pub preference_penalty: i32,
+    pub predictions: Predictions,
+    /// Teammate pairs that already played together weighted by recency, see
+    /// `TeammateHistory::total_repeats`.
+    pub repeats: u32,
+    /// Low ranked players were placed next to each other to fill teams, despite the rank limiter.
+    pub rank_limiter_relaxed: bool,
+    /// Sub roles were taken twice to fill teams, despite duplicate roles being disallowed.
+    pub duplicate_roles_relaxed: bool,
+    /// Broken invariants, only filled in when results are checked, see `check_result`.
+    pub violations: Vec<Violation>,
+    /// Mixer rounds only, false when no try kept dispersion within the tolerance.
+    pub within_tolerance: Option<bool>,
+}
+
+/// Priority of the role a player was placed on, `None` if it is not among their active roles.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RolePreference {
+    pub uuid: String,
+    pub name: String,
+    pub role: SimpleRole,
+    pub priority: Option<usize>,
+    pub is_full_flex: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Preferences {
+    pub players: Vec<RolePreference>,
+    /// Share of placed players per priority, index 0 is priority 1.
+    pub priority_shares: Vec<f32>,
+    /// Share of placed players on a role they did not pick.
+    pub unlisted_share: f32,
+    /// Full-flex players placed on a role other than their first.
+    pub full_flex_used: usize,
+}
+
+/// Steps of `balance_players`, in order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    Init,
+    Squires,
+    Pool,
+    Lieutenants,
+    Ensigns,
+    Fillers,
+    Remaining,
+    SwapSteal,
+    IncreaseQuality,
+    MinimizeDispersion,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 10] = [
+        Phase::Init,
+        Phase::Squires,
+        Phase::Pool,
+        Phase::Lieutenants,
+        Phase::Ensigns,
+        Phase::Fillers,
+        Phase::Remaining,
+        Phase::SwapSteal,
+        Phase::IncreaseQuality,
+        Phase::MinimizeDispersion,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Phase::Init => "Init",
+            Phase::Squires => "Distribute squires",
+            Phase::Pool => "Init pool",
+            Phase::Lieutenants => "Distribute lieutenants",
+            Phase::Ensigns => "Distribute ensigns",
+            Phase::Fillers => "Distribute fillers",
+            Phase::Remaining => "Distribute remaining",
+            Phase::SwapSteal => "Swap Steal",
+            Phase::IncreaseQuality => "Increase quality",
+            Phase::MinimizeDispersion => "Minimize dispersion",
+        }
+    }
 }
 
 impl<'a> Matchmaking<'a> {
@@ -47,7 +157,7 @@ impl<'a> Matchmaking<'a> {
         rank_limiter: bool,
         duplicate_roles: bool,
     ) -> Matchmaking {
-        let config = Config::new(tolerance, rank_limiter, duplicate_roles);
+        let config = Config::new(players, tolerance, rank_limiter, duplicate_roles);
 
         Matchmaking {
             config,
@@ -55,8 +165,11 @@ impl<'a> Matchmaking<'a> {
             teams: Teams::default(),
             balanced: Vec::default(),
             pool: PlayerPool::default(),
-            reserve_pool: PlayerPool::default(),
+            reserve: Reserve::default(),
             disable_type: String::from("none"),
+            rng: StdRng::from_entropy(),
+            checked: false,
+            relaxed: Relaxations::default(),
         }
     }
 
@@ -64,52 +177,77 @@ impl<'a> Matchmaking<'a> {
         self.disable_type = disable_type;
     }
 
+    /// Same players and seed always give the same teams.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    pub fn set_composition(&mut self, composition: Composition) {
+        self.config.composition = composition;
+    }
+
+    pub fn set_preference_weight(&mut self, weight: u32) {
+        self.config.preference_weight = weight;
+    }
+
+    pub fn set_predictor(&mut self, predictor: Predictor) {
+        self.config.predictor = predictor;
+    }
+
+    /// Teammates of earlier rounds, placements prefer players who have not met yet.
+    pub fn set_history(&mut self, history: TeammateHistory) {
+        self.config.arena.set_history(&history);
+        self.config.history = history;
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Results are checked against `check_result` before they are returned.
+    pub fn set_checked(&mut self, checked: bool) {
+        self.checked = checked;
+    }
+
     pub fn enable_dispersion_minimizer(&mut self) {
         self.config.dispersion_minimizer = true;
     }
 
     pub fn balance_players(&mut self) {
-        self.log("Init");
-        self.init_teams();
-        self.log("Distribute squires");
-        if self.disable_type.as_str() != "ex_caps" {
-            self.distribute_squires();
-        }
-        self.log("Init pool");
-        self.init_pool(false);
-
-        self.log("Distribute lieutenants");
-        if self.disable_type.as_str() != "ex_caps" && self.disable_type.as_str() != "leut_ens" {
-            self.distribute_lieutenants();
-        }
+        for &phase in Phase::ALL.iter() {
+            self.log(phase.label());
 
-        self.log("Distribute ensigns");
-        if self.disable_type.as_str() != "ex_caps"
-            && self.disable_type.as_str() != "leut_ens"
-            && self.disable_type.as_str() != "ens"
-        {
-            self.distribute_ensigns();
+            if self.is_enabled(phase) {
+                self.run_phase(phase);
+            }
         }
 
-        self.log("Distribute fillers");
-        self.distribute_fillers();
-        self.log("Distribute remaining");
-        self.distribute_remaining();
-        self.log("Swap Steal");
-        self.swap_steal();
-        self.log("Increase quality");
-        self.increase_quality();
-        self.log("Minimize dispersion");
-        self.minimize_dispersion();
         self.teams.sort(Direction::ASC);
     }
 
+    /// Runs one step of `balance_players`, regardless of the disable type.
+    pub fn run_phase(&mut self, phase: Phase) {
+        match phase {
+            Phase::Init => self.init_teams(),
+            Phase::Squires => self.distribute_squires(),
+            Phase::Pool => self.init_pool(false),
+            Phase::Lieutenants => self.distribute_lieutenants(),
+            Phase::Ensigns => self.distribute_ensigns(),
+            Phase::Fillers => self.distribute_fillers(),
+            Phase::Remaining => self.distribute_remaining(),
+            Phase::SwapSteal => self.swap_steal(),
+            Phase::IncreaseQuality => self.increase_quality(),
+            Phase::MinimizeDispersion => self.minimize_dispersion(),
+        }
+    }
+
     pub fn balance_half(&mut self) {
         self.init_teams();
         self.distribute_squires();
         self.init_pool(false);
         self.distribute_lieutenants();
         self.distribute_ensigns();
+        self.teams.update(&self.config);
         self.teams.sort(Direction::ASC);
     }
 
@@ -122,17 +260,35 @@ impl<'a> Matchmaking<'a> {
         self.teams.sort(Direction::ASC);
     }
 
-    pub fn result(self) -> BalancerResult {
+    pub fn result(mut self) -> BalancerResult {
+        self.teams.update_deviation(self.players);
         let dispersion = self.calculate_dispersion();
         let lows = self.teams.total_low_role_count(self.config.limiter_max);
-        BalancerResult::new(self.teams, self.pool, dispersion, lows)
+        let preferences = Preferences::new(&self.teams, self.players);
+        let preference_penalty = preferences.penalty(self.config.preference_weight);
+        let predictions = self.config.predictor.predict(&self.teams);
+        let repeats = self.config.history.total_repeats(&self.teams);
+        let mut result = BalancerResult::new(self.teams, self.pool, dispersion, lows);
+        result.preferences = preferences;
+        result.preference_penalty = preference_penalty;
+        result.predictions = predictions;
+        result.repeats = repeats;
+        result.rank_limiter_relaxed = self.relaxed.rank_limiter;
+        result.duplicate_roles_relaxed = self.relaxed.duplicate_roles;
+
+        if self.checked {
+            result.violations = check_result(self.players, &self.config, &result);
+        }
+
+        result
     }
 
     pub fn add_reserve(&mut self, reserve: Vec<String>) {
         self.balanced = reserve;
     }
 
-    pub fn add_teams(&mut self, teams: Teams) {
+    pub fn add_teams(&mut self, mut teams: Teams) {
+        self.config.arena.index_teams(&mut teams);
         self.teams = teams;
     }
 
@@ -153,6 +309,19 @@ impl<'a> Matchmaking<'a> {
         cmp::max(low_disp, high_disp)
     }
 
+    fn is_enabled(&self, phase: Phase) -> bool {
+        let disable_type = self.disable_type.as_str();
+
+        match phase {
+            Phase::Squires => disable_type != "ex_caps",
+            Phase::Lieutenants => disable_type != "ex_caps" && disable_type != "leut_ens",
+            Phase::Ensigns => {
+                disable_type != "ex_caps" && disable_type != "leut_ens" && disable_type != "ens"
+            }
+            _ => true,
+        }
+    }
+
     #[allow(unused_unsafe)]
     fn log(&self, message: &str) {
         unsafe {
@@ -165,13 +334,15 @@ impl<'a> Matchmaking<'a> {
     }
 
     fn init_pool(&mut self, invert: bool) {
-        self.players.feed(&mut self.pool, &self.balanced, invert);
+        self.config
+            .arena
+            .feed(&mut self.pool, &self.balanced, invert);
         self.pool.sort_by_rank(Direction::ASC);
-        self.reserve_pool = self.pool.clone();
+        self.reserve = Reserve::new(&self.pool, &self.config.arena);
     }
 
     fn init_teams(&mut self) {
-        let mut captains = self.players.get_captains();
+        let mut captains = self.config.arena.get_captains();
         captains.sort_by_rank(Direction::ASC);
         self.preserve_players(&captains);
 
@@ -179,7 +350,7 @@ impl<'a> Matchmaking<'a> {
     }
 
     fn minimize_dispersion(&mut self) {
-        let not_complete_teams = self.teams.get_not_complete();
+        let not_complete_teams = self.teams.get_not_complete(&self.config);
         if !self.config.dispersion_minimizer
             || (self.config.dispersion_minimizer && not_complete_teams.len() > 0)
         {
@@ -193,26 +364,22 @@ impl<'a> Matchmaking<'a> {
             let second = self.teams.0[swap.2].members.remove(swap.3);
             self.teams.0[swap.2].members.push(first);
             self.teams.0[swap.0].members.push(second);
-            self.teams.0[swap.2].update();
-            self.teams.0[swap.0].update();
+            self.teams.0[swap.2].update(&self.config);
+            self.teams.0[swap.0].update(&self.config);
         }
     }
 
     fn update_role_avg(&mut self) {
-        let role_players = self.teams.teams_count() * 2;
-
-        self.config.roles_avg.insert(
-            "dps".to_string(),
-            (self.teams.total_role_sr(&SimpleRole::Dps)) / role_players as i32,
-        );
-        self.config.roles_avg.insert(
-            "support".to_string(),
-            self.teams.total_role_sr(&SimpleRole::Support) / role_players as i32,
-        );
-        self.config.roles_avg.insert(
-            "tank".to_string(),
-            self.teams.total_role_sr(&SimpleRole::Tank) / (role_players / 2) as i32,
-        );
+        let teams_count = self.teams.teams_count();
+
+        for role in self.config.composition.roles() {
+            let role_players = (teams_count * self.config.composition.slots(role)).max(1);
+
+            self.config.roles_avg.insert(
+                role.clone(),
+                self.teams.total_role_sr(role) / role_players as i32,
+            );
+        }
     }
 
     fn try_minimize(&self) -> Option<(usize, usize, usize, usize)> {
@@ -220,7 +387,7 @@ impl<'a> Matchmaking<'a> {
 
         for (t1, team) in teams.iter().enumerate() {
             for (t2, team2) in teams.iter().rev().enumerate() {
-                if let Some((a, b)) = team.can_swap(&team2, &self.config, &self.players) {
+                if let Some((a, b)) = team.can_swap(team2, &self.config) {
                     return Some((t1, a, teams.len() - t2 - 1, b));
                 }
             }
@@ -230,15 +397,15 @@ impl<'a> Matchmaking<'a> {
     }
 
     fn swap_steal(&mut self) {
-        self.teams.update();
-        let not_complete_teams = self.teams.get_not_complete();
+        self.teams.update(&self.config);
+        let not_complete_teams = self.teams.get_not_complete(&self.config);
         let not_complete_len = not_complete_teams.len();
 
         let mut inserted = 0;
 
         for id in not_complete_teams {
             let team = self.teams.get(id);
-            let role = team.get_missing_role();
+            let role = team.get_missing_role(&self.config);
             if role.is_none() {
                 continue;
             }
@@ -250,8 +417,8 @@ impl<'a> Matchmaking<'a> {
                 role,
                 range,
                 &self.teams,
-                &self.reserve_pool,
-                &team.clone(),
+                &self.reserve,
+                team,
                 &self.config,
             );
 
@@ -259,28 +426,21 @@ impl<'a> Matchmaking<'a> {
                 let replacement_team = self.teams.get(team_id);
                 let replacement_member = replacement_team.members.get(replacement_id).unwrap();
                 let replacement = self
-                    .reserve_pool
-                    .0
-                    .iter()
-                    .find(|candidate| candidate.uuid.as_str() == replacement_member.uuid.as_str())
+                    .reserve
+                    .get(&self.config.arena, replacement_member.index)
                     .unwrap();
 
                 let team = self.teams.get_mut(id);
+                let replacement_role = replacement.roles.get_by_simple(&role_clone).unwrap();
 
-                team.add_player(
-                    &replacement,
-                    replacement.roles.get_by_simple(&role_clone).unwrap(),
-                );
-                team.update();
+                self.relaxed.note(team, replacement_role, &self.config);
+                team.add_player(&replacement, replacement_role);
+                team.update(&self.config);
 
                 let team = self.teams.get_mut(team_id);
                 let replacement_member = team.members.remove(replacement_id);
 
-                let pos = self
-                    .pool
-                    .0
-                    .iter()
-                    .position(|c| c.uuid.as_str() == leftover.uuid.as_str());
+                let pos = self.pool.0.iter().position(|c| c == leftover);
                 if let Some(index) = pos {
                     let candidate = self.pool.0.get(index).unwrap();
                     let add_role = candidate
@@ -288,10 +448,11 @@ impl<'a> Matchmaking<'a> {
                         .get_by_simple(&replacement_member.role)
                         .unwrap();
 
+                    self.relaxed.note(team, add_role, &self.config);
                     team.add_player(candidate, &add_role);
-                    team.update();
+                    team.update(&self.config);
                     self.config.total_count += 1;
-                    self.config.total_sr += add_role.decompose().1;
+                    self.config.total_sr += add_role.strength(&self.config);
                     self.pool.0.remove(index);
                     inserted += 1;
                 }
@@ -318,71 +479,59 @@ impl<'a> Matchmaking<'a> {
         let average = self.config.total_sr / self.config.total_count as i32;
         let cl = self.teams.clone();
 
-        let low_supports: Vec<(usize, &Team)> = cl
-            .0
-            .iter()
-            .enumerate()
-            .filter(|(_, team)| {
-                team.members_count() == 5 && team.low_support_count(self.config.limiter_max) == 2
-            })
-            .collect();
-
-        let low_tanks: Vec<(usize, &Team)> = Vec::new();
-
-        let low_dps: Vec<(usize, &Team)> =
-            cl.0.iter()
-                .enumerate()
-                .filter(|(_, team)| {
-                    team.members_count() == 5 && team.low_dps_count(self.config.limiter_max) == 2
-                })
-                .collect();
+        for role in self.config.composition.roles() {
+            let slots = self.config.composition.slots(role);
 
-        for (li, ls) in &low_supports {
-            let res = self.t_find(SimpleRole::Support, *li, &ls, average);
-            if let Some(rep) = res {
-                self.teams.swap(rep.0, rep.1, rep.2, rep.3);
+            // A single slot role can not be traded for a stronger partner
+            if slots < 2 {
+                continue;
             }
-        }
 
-        for (li, ls) in &low_tanks {
-            let res = self.t_find(SimpleRole::Tank, *li, &ls, average);
-            if let Some(rep) = res {
-                self.teams.swap(rep.0, rep.1, rep.2, rep.3);
-            }
-        }
+            let low_teams: Vec<(usize, &Team)> =
+                cl.0.iter()
+                    .enumerate()
+                    .filter(|(_, team)| {
+                        team.is_complete(&self.config)
+                            && team.low_role_count(role, self.config.limiter_max) == slots
+                    })
+                    .collect();
 
-        for (li, ls) in &low_dps {
-            let res = self.t_find(SimpleRole::Dps, *li, &ls, average);
-            if let Some(rep) = res {
-                self.teams.swap(rep.0, rep.1, rep.2, rep.3);
+            for (li, ls) in &low_teams {
+                let res = self.t_find(role, *li, &ls, average);
+                if let Some(rep) = res {
+                    self.teams.swap(rep.0, rep.1, rep.2, rep.3, &self.config);
+                }
             }
         }
     }
 
     fn t_find(
         &self,
-        role: SimpleRole,
+        role: &SimpleRole,
         li: usize,
         ls: &Team,
         average: i32,
     ) -> Option<(usize, usize, usize, usize)> {
-        let high_supports: Vec<(usize, &Team)> = self
+        let high_teams: Vec<(usize, &Team)> = self
             .teams
             .0
             .iter()
             .enumerate()
             .filter(|(_, team)| {
-                team.members_count() == 5 && team.low_support_count(self.config.limiter_max) == 0
+                team.is_complete(&self.config)
+                    && team.low_role_count(role, self.config.limiter_max) == 0
             })
             .collect();
 
-        for (hi, hs) in &high_supports {
-            // by role
+        for (hi, hs) in &high_teams {
+            // by role, captains and squires stay with their teams
             let ls_members: Vec<(usize, &Member)> = ls
                 .members
                 .iter()
                 .enumerate()
-                .filter(|&member| member.1.role == role)
+                .filter(|&member| {
+                    member.1.role == *role && !self.config.arena.is_anchor(member.1.index)
+                })
                 .collect();
             for lm in ls_members {
                 // by role
@@ -390,11 +539,23 @@ impl<'a> Matchmaking<'a> {
                     .members
                     .iter()
                     .enumerate()
-                    .filter(|&member| member.1.role == role)
+                    .filter(|&member| {
+                        member.1.role == *role && !self.config.arena.is_anchor(member.1.index)
+                    })
                     .collect();
                 for hm in hs_members {
-                    let new_sr_l = (ls.total_sr - lm.1.rank + hm.1.rank) / 5;
-                    let new_sr_h = (hs.total_sr - hm.1.rank + lm.1.rank) / 5;
+                    if self.config.duplicate_roles2
+                        && (ls.duplicates_sub_role(lm.0, hm.1)
+                            || hs.duplicates_sub_role(hm.0, lm.1))
+                    {
+                        continue;
+                    }
+
+                    let team_size = self.config.composition.team_size() as i32;
+                    let low_strength = lm.1.strength(&self.config);
+                    let high_strength = hm.1.strength(&self.config);
+                    let new_sr_l = (ls.total_sr - low_strength + high_strength) / team_size;
+                    let new_sr_h = (hs.total_sr - high_strength + low_strength) / team_size;
                     if (new_sr_l - average).abs() <= self.config.tolerance as i32
                         && (new_sr_h - average).abs() <= self.config.tolerance as i32
                     {
@@ -411,26 +572,26 @@ impl<'a> Matchmaking<'a> {
 
     fn distribute_remaining(&mut self) {
         self.update();
-        self.pool.shuffle();
+        self.pool.shuffle(&mut self.rng);
         self.pool.sort_full_flex();
         self.teams.sort(Direction::ASC);
         self.sort_remaining(1);
     }
 
     fn distribute_squires(&mut self) {
-        let mut squires = self.players.get_squires();
+        let mut squires = self.config.arena.get_squires();
         squires.sort_by_rank(Direction::ASC);
         self.preserve_players(&squires);
 
-        self.teams.distribute_squires_to_tanks(&mut squires);
-        self.teams.distribute_squires_to_dps(&mut squires);
-        self.teams.update();
+        self.teams
+            .distribute_squires_by_role(&mut squires, &self.config);
+        self.teams.update(&self.config);
         self.teams.sort(Direction::DESC);
-        self.teams.distribute_squires(&mut squires);
+        self.teams.distribute_squires(&mut squires, &self.config);
 
         if squires.0.len() > 0 {
-            self.balanced
-                .retain(|uuid| !squires.0.iter().any(|c| &c.uuid == uuid));
+            let unplaced = squires.collect_ids();
+            self.balanced.retain(|uuid| !unplaced.contains(uuid));
         }
     }
 
@@ -441,14 +602,14 @@ impl<'a> Matchmaking<'a> {
     }
 
     fn distribute_ensigns(&mut self) {
-        self.teams.update();
+        self.teams.update(&self.config);
         self.teams.sort(Direction::ASC);
         self.pool.sort_by_rank(Direction::ASC);
         self.teams.distribute_ensigns(&mut self.pool, &self.config);
     }
 
     fn distribute_fillers(&mut self) {
-        self.teams.update();
+        self.teams.update(&self.config);
         self.teams.sort(Direction::DESC);
         self.calculate_players_average();
 
@@ -458,7 +619,7 @@ impl<'a> Matchmaking<'a> {
     }
 
     fn update(&mut self) {
-        self.teams.update();
+        self.teams.update(&self.config);
         let (total_sr, total_count) = self.teams.get_stats();
 
         self.config.total_sr = total_sr;
@@ -486,16 +647,16 @@ impl<'a> Matchmaking<'a> {
     fn fit_players(&mut self) -> usize {
         let mut leftovers: PlayerPool = PlayerPool::default();
         let mut added_players = 0;
-        let pool = &self.pool.0.clone();
+        let pool = std::mem::take(&mut self.pool);
 
-        for candidate in pool {
-            let fits = self.fit_player(candidate);
+        for candidate in pool.0 {
+            let fits = self.fit_player(&candidate);
             if let Some(sr) = fits {
                 self.config.total_sr += sr;
                 self.config.total_count += 1;
                 added_players += 1;
             } else {
-                leftovers.add_candidate(candidate);
+                leftovers.0.push(candidate);
             }
         }
 
@@ -518,15 +679,16 @@ impl<'a> Matchmaking<'a> {
         for role_index in 0..roles_count {
             let target_role = candidate.roles.get(role_index);
 
-            let (_, player_sr) = target_role.decompose();
+            let player_sr = target_role.strength(&self.config);
             let new_average =
                 (self.config.total_sr + player_sr) as f32 / (self.config.total_count + 1) as f32;
             if let Some(team) =
                 self.teams
-                    .fit_player(player_sr, new_average, &self.config, target_role)
+                    .fit_player(player_sr, new_average, &self.config, candidate, target_role)
             {
+                self.relaxed.note(team, target_role, &self.config);
                 team.add_player(candidate, target_role);
-                team.update();
+                team.update(&self.config);
 
                 return Some(player_sr);
             }
@@ -537,12 +699,23 @@ impl<'a> Matchmaking<'a> {
 
     fn calculate_players_average(&mut self) {
         let (total_sr, total_count) = self.teams.get_stats();
-        self.config.players_average = self.pool.get_primary_average(total_sr, total_count);
+        self.config.players_average =
+            self.pool
+                .get_primary_average(total_sr, total_count, &self.config);
+    }
+}
+
+impl Relaxations {
+    /// Notes placing `role` in `team` if it breaks a setting the caller asked for.
+    fn note(&mut self, team: &Team, role: &Role, config: &Config) {
+        self.rank_limiter |= config.rank_limiter2 && role.is_limited(team, config);
+        self.duplicate_roles |=
+            config.duplicate_roles2 && team.has_sub_role_duplicate(&role.role, role.sub_role);
     }
 }
 
 impl Config {
-    fn new(tolerance: u32, rank_limiter: bool, duplicate_roles: bool) -> Config {
+    fn new(players: &Players, tolerance: u32, rank_limiter: bool, duplicate_roles: bool) -> Config {
         Config {
             tolerance,
             rank_limiter,
@@ -553,6 +726,11 @@ impl Config {
             limiter_max: 600,
             players_average: 0,
             roles_avg: HashMap::new(),
+            composition: Composition::default(),
+            preference_weight: 0,
+            predictor: Predictor::default(),
+            history: TeammateHistory::default(),
+            arena: Arena::new(players),
             rank_limiter2: rank_limiter,
             dispersion_minimizer: false,
             duplicate_roles2: duplicate_roles,
@@ -563,10 +741,223 @@ impl Config {
 impl BalancerResult {
     fn new(teams: Teams, leftovers: PlayerPool, dispersion: i32, lows: usize) -> BalancerResult {
         BalancerResult {
+            duplicates: teams.sub_role_duplicates(),
             teams,
             leftovers,
             dispersion,
             anchors: lows as i32,
+            occurrences: 1,
+            preferences: Preferences::default(),
+            preference_penalty: 0,
+            predictions: Predictions::default(),
+            repeats: 0,
+            rank_limiter_relaxed: false,
+            duplicate_roles_relaxed: false,
+            violations: Vec::default(),
+            within_tolerance: None,
+        }
+    }
+
+    /// Collapses equivalent results into one, counting how many times each appeared.
+    pub fn distinct(results: Vec<BalancerResult>) -> Vec<BalancerResult> {
+        let mut distinct: Vec<BalancerResult> = Vec::default();
+        let mut seen: HashMap<Vec<Vec<(String, SimpleRole)>>, usize> = HashMap::new();
+
+        for result in results {
+            let key = result.teams.canonical();
+
+            if let Some(&index) = seen.get(&key) {
+                distinct[index].occurrences += result.occurrences;
+            } else {
+                seen.insert(key, distinct.len());
+                distinct.push(result);
+            }
+        }
+
+        distinct
+    }
+
+    /// Picks up to `count` best results that differ from each other in at least
+    /// `min_difference` player placements. A `count` of 0 keeps every distinct result.
+    pub fn select_diverse(
+        results: Vec<BalancerResult>,
+        count: usize,
+        min_difference: usize,
+    ) -> Vec<BalancerResult> {
+        let mut candidates = BalancerResult::distinct(results);
+        candidates.sort_by_key(|result| result.objective());
+
+        if count == 0 {
+            return candidates;
+        }
+
+        let mut selected: Vec<BalancerResult> = Vec::default();
+
+        for candidate in candidates {
+            if selected.len() >= count {
+                break;
+            }
+
+            let is_diverse = selected
+                .iter()
+                .all(|result| result.difference(&candidate) >= min_difference);
+
+            if is_diverse {
+                selected.push(candidate);
+            }
         }
+
+        selected
+    }
+
+    /// Picks the mixer round with the fewest teammate repeats, among the results with
+    /// the fewest leftovers that keep dispersion within `tolerance` when any does.
+    /// `None` only for no results.
+    pub fn select_round(
+        results: Vec<BalancerResult>,
+        tolerance: u32,
+        history: &TeammateHistory,
+    ) -> Option<BalancerResult> {
+        let mut results: Vec<BalancerResult> = results
+            .into_iter()
+            .map(|mut result| {
+                result.repeats = history.total_repeats(&result.teams);
+                result
+            })
+            .collect();
+
+        let leftovers = results.iter().map(|result| result.leftovers.size()).min()?;
+        results.retain(|result| result.leftovers.size() == leftovers);
+
+        if results
+            .iter()
+            .any(|result| result.dispersion <= tolerance as i32)
+        {
+            results.retain(|result| result.dispersion <= tolerance as i32);
+        }
+
+        let mut round = results
+            .into_iter()
+            .min_by_key(|result| (result.repeats, result.dispersion, result.anchors))?;
+        round.within_tolerance = Some(round.dispersion <= tolerance as i32);
+
+        Some(round)
+    }
+
+    /// Lower is better: fewer leftovers first, then dispersion with the preference
+    /// penalty, then anchors.
+    pub fn objective(&self) -> (usize, i32, i32) {
+        (
+            self.leftovers.size(),
+            self.dispersion.saturating_add(self.preference_penalty),
+            self.anchors,
+        )
+    }
+
+    /// Number of players placed in a different team or role than in `other`.
+    /// Teams are identified by their captain, so team order and uuids do not matter.
+    pub fn difference(&self, other: &BalancerResult) -> usize {
+        let placements = self.placements();
+        let other_placements = other.placements();
+
+        placements
+            .iter()
+            .filter(|(uuid, placement)| other_placements.get(*uuid) != Some(placement))
+            .count()
+    }
+
+    fn placements(&self) -> HashMap<&str, (&str, &SimpleRole)> {
+        let mut placements = HashMap::new();
+
+        for team in &self.teams.0 {
+            let captain = team.get_captain().uuid.as_str();
+
+            for member in &team.members {
+                placements.insert(member.uuid.as_str(), (captain, &member.role));
+            }
+        }
+
+        placements
+    }
+}
+
+impl Preferences {
+    pub fn new(teams: &Teams, players: &Players) -> Preferences {
+        let mut preferences = Preferences::default();
+
+        for team in &teams.0 {
+            for member in &team.members {
+                let player = players.get_player(&member.uuid);
+                let priority = player.and_then(|player| {
+                    Roles::from(&player.stats.classes).priority_of(&member.role)
+                });
+                let is_full_flex =
+                    player.and_then(|player| player.identity.is_full_flex) == Some(true);
+
+                preferences.players.push(RolePreference {
+                    uuid: member.uuid.clone(),
+                    name: member.name.clone(),
+                    role: member.role.clone(),
+                    priority,
+                    is_full_flex,
+                });
+            }
+        }
+
+        preferences.update_stats();
+        preferences
+    }
+
+    fn update_stats(&mut self) {
+        let total = self.players.len();
+
+        if total == 0 {
+            return;
+        }
+
+        let max_priority = self
+            .players
+            .iter()
+            .filter_map(|player| player.priority)
+            .max()
+            .unwrap_or(0);
+        let mut counts = vec![0; max_priority];
+        let mut unlisted = 0;
+
+        for player in &self.players {
+            match player.priority {
+                Some(priority) => counts[priority - 1] += 1,
+                None => unlisted += 1,
+            }
+        }
+
+        self.priority_shares = counts
+            .iter()
+            .map(|count| *count as f32 / total as f32)
+            .collect();
+        self.unlisted_share = unlisted as f32 / total as f32;
+        self.full_flex_used = self
+            .players
+            .iter()
+            .filter(|player| player.is_full_flex && player.priority != Some(1))
+            .count();
+    }
+
+    /// `weight` rating points per priority step below the preferred role. Unlisted roles
+    /// count as one step past the lowest priority. Saturates at `i32::MAX`.
+    pub fn penalty(&self, weight: u32) -> i32 {
+        let steps: usize = self
+            .players
+            .iter()
+            .map(|player| {
+                player
+                    .priority
+                    .map_or(self.priority_shares.len(), |priority| priority - 1)
+            })
+            .sum();
+
+        let penalty = (steps as u64).saturating_mul(weight as u64);
+
+        penalty.min(i32::MAX as u64) as i32
     }
 }
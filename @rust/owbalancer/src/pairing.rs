@@ -0,0 +1,500 @@
+use crate::teams::{Team, Teams};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// Pairings the Swiss search tries before it gives up on avoiding rematches.
+const SWISS_STEPS: usize = 100_000;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum PairingOptions {
+    /// Teams are snake-seeded into `groups` groups that play all-play-all.
+    #[serde(rename_all = "camelCase")]
+    RoundRobin { groups: usize },
+    /// Swiss round `number`. Teams with equal points are paired by closest SR and
+    /// pairs from `played` are avoided whenever possible.
+    #[serde(rename_all = "camelCase")]
+    Swiss {
+        number: usize,
+        #[serde(default)]
+        played: Vec<(String, String)>,
+        #[serde(default)]
+        points: Vec<(String, f32)>,
+        #[serde(default)]
+        byes: Vec<String>,
+    },
+    /// First round of an elimination bracket seeded by SR, top seeds receive the byes.
+    /// A `double` elimination bracket also lays out the losers bracket.
+    Elimination { double: bool },
+}
+
+/// Match between two teams identified by uuid. `away` is `None` for a bye.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Pairing {
+    pub home: String,
+    pub away: Option<String>,
+    pub sr_gap: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Round {
+    pub number: usize,
+    pub pairings: Vec<Pairing>,
+    /// Pairings that repeat a played match because every other pairing did too.
+    #[serde(default)]
+    pub rematches: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Group {
+    pub teams: Vec<String>,
+    pub rounds: Vec<Round>,
+}
+
+/// Team of a bracket match that is decided by an earlier match. Rounds are 1-based,
+/// `index` is the 0-based position of the match in its round.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Slot {
+    WinnersWinner {
+        round: usize,
+        index: usize,
+    },
+    /// Team that drops down into the losers bracket.
+    WinnersLoser {
+        round: usize,
+        index: usize,
+    },
+    LosersWinner {
+        round: usize,
+        index: usize,
+    },
+}
+
+/// Match fed by earlier matches, an empty slot is a bye.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BracketMatch {
+    pub home: Option<Slot>,
+    pub away: Option<Slot>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LosersRound {
+    pub number: usize,
+    pub matches: Vec<BracketMatch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Bracket {
+    pub size: usize,
+    pub double: bool,
+    /// Team uuids by seed, the first one is the strongest team.
+    pub seeds: Vec<String>,
+    pub first_round: Round,
+    /// Empty for single elimination.
+    #[serde(default)]
+    pub losers_rounds: Vec<LosersRound>,
+    /// Winners bracket champion against the losers bracket champion, double elimination only.
+    #[serde(default)]
+    pub grand_final: Option<BracketMatch>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "mode", rename_all = "camelCase")]
+pub enum Schedule {
+    RoundRobin { groups: Vec<Group> },
+    Swiss { round: Round },
+    Elimination { bracket: Bracket },
+}
+
+impl Pairing {
+    fn new(home: &Team, away: Option<&Team>) -> Pairing {
+        Pairing {
+            home: home.uuid.to_string(),
+            away: away.map(|team| team.uuid.to_string()),
+            sr_gap: away.map_or(0.0, |team| (home.avg_sr - team.avg_sr).abs()),
+        }
+    }
+}
+
+impl Schedule {
+    pub fn new(teams: &Teams, options: &PairingOptions) -> Schedule {
+        match options {
+            PairingOptions::RoundRobin { groups } => Schedule::RoundRobin {
+                groups: Schedule::round_robin(teams, *groups),
+            },
+            PairingOptions::Swiss {
+                number,
+                played,
+                points,
+                byes,
+            } => Schedule::Swiss {
+                round: Schedule::swiss(teams, *number, played, points, byes),
+            },
+            PairingOptions::Elimination { double } => Schedule::Elimination {
+                bracket: Schedule::elimination(teams, *double),
+            },
+        }
+    }
+
+    fn round_robin(teams: &Teams, groups_count: usize) -> Vec<Group> {
+        let groups_count = groups_count.max(1).min(teams.0.len().max(1));
+        let mut seeded: Vec<Vec<&Team>> = vec![Vec::default(); groups_count];
+
+        let mut reverse = false;
+
+        for lap in teams.seeded().chunks(groups_count) {
+            for (offset, team) in lap.iter().enumerate() {
+                let group = if reverse {
+                    groups_count - 1 - offset
+                } else {
+                    offset
+                };
+
+                seeded[group].push(team);
+            }
+
+            reverse = !reverse;
+        }
+
+        seeded
+            .into_iter()
+            .map(|group| Group {
+                teams: group.iter().map(|team| team.uuid.to_string()).collect(),
+                rounds: Schedule::circle(&group),
+            })
+            .collect()
+    }
+
+    /// Circle method, every team meets every other team once.
+    fn circle(teams: &[&Team]) -> Vec<Round> {
+        let mut slots: Vec<Option<&Team>> = teams.iter().map(|team| Some(*team)).collect();
+
+        if slots.len() % 2 == 1 {
+            slots.push(None);
+        }
+
+        let size = slots.len();
+        let mut rounds = Vec::default();
+
+        for number in 1..size {
+            let mut pairings = Vec::default();
+
+            for i in 0..size / 2 {
+                match (slots[i], slots[size - 1 - i]) {
+                    (Some(home), away) => pairings.push(Pairing::new(home, away)),
+                    (None, Some(home)) => pairings.push(Pairing::new(home, None)),
+                    (None, None) => {}
+                }
+            }
+
+            rounds.push(Round {
+                number,
+                pairings,
+                ..Round::default()
+            });
+            slots[1..].rotate_right(1);
+        }
+
+        rounds
+    }
+
+    fn swiss(
+        teams: &Teams,
+        number: usize,
+        played: &[(String, String)],
+        points: &[(String, f32)],
+        byes: &[String],
+    ) -> Round {
+        let played: HashSet<(String, String)> = played
+            .iter()
+            .flat_map(|(a, b)| vec![(a.clone(), b.clone()), (b.clone(), a.clone())])
+            .collect();
+        let points_of = |team: &Team| {
+            let uuid = team.uuid.to_string();
+            points
+                .iter()
+                .find(|(id, _)| *id == uuid)
+                .map_or(0.0, |(_, points)| *points)
+        };
+        let is_rematch = |home: &Team, away: &Team| {
+            played.contains(&(home.uuid.to_string(), away.uuid.to_string()))
+        };
+
+        let mut standings = teams.seeded();
+        standings.sort_by(|a, b| {
+            points_of(b)
+                .partial_cmp(&points_of(a))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let mut pairings = Vec::default();
+
+        if standings.len() % 2 == 1 {
+            let bye = standings
+                .iter()
+                .rposition(|team| !byes.contains(&team.uuid.to_string()))
+                .unwrap_or(standings.len() - 1);
+
+            pairings.push(Pairing::new(standings.remove(bye), None));
+        }
+
+        let mut steps = SWISS_STEPS;
+        let mut rematches = 0;
+        let pairs = match Schedule::pair_swiss(&standings, &points_of, &is_rematch, &mut steps) {
+            Some(pairs) => pairs,
+            None => {
+                // Every pairing repeats a match, the closest opponents are taken in order
+                let mut pairs = Vec::default();
+
+                while !standings.is_empty() {
+                    let home = standings.remove(0);
+                    let opponents = Schedule::opponents(home, &standings, &points_of);
+                    let opponent = opponents
+                        .iter()
+                        .find(|&&index| !is_rematch(home, standings[index]))
+                        .unwrap_or(&opponents[0]);
+                    let away = standings.remove(*opponent);
+
+                    if is_rematch(home, away) {
+                        rematches += 1;
+                    }
+
+                    pairs.push((home, away));
+                }
+
+                pairs
+            }
+        };
+
+        for (home, away) in pairs {
+            pairings.push(Pairing::new(home, Some(away)));
+        }
+
+        Round {
+            number,
+            pairings,
+            rematches,
+        }
+    }
+
+    /// Pairs the first team with the closest opponent that lets every other team avoid a
+    /// rematch, backtracking to the next closest one otherwise. `None` when every pairing
+    /// has a rematch or the search runs out of `steps`.
+    fn pair_swiss<'a>(
+        standings: &[&'a Team],
+        points_of: &dyn Fn(&Team) -> f32,
+        is_rematch: &dyn Fn(&Team, &Team) -> bool,
+        steps: &mut usize,
+    ) -> Option<Vec<(&'a Team, &'a Team)>> {
+        let (home, rest) = match standings.split_first() {
+            Some(split) => split,
+            None => return Some(Vec::default()),
+        };
+
+        for opponent in Schedule::opponents(home, rest, points_of) {
+            if *steps == 0 {
+                return None;
+            }
+
+            *steps -= 1;
+
+            if is_rematch(home, rest[opponent]) {
+                continue;
+            }
+
+            let remaining: Vec<&Team> = rest
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != opponent)
+                .map(|(_, team)| *team)
+                .collect();
+
+            if let Some(mut pairs) = Schedule::pair_swiss(&remaining, points_of, is_rematch, steps)
+            {
+                pairs.insert(0, (*home, rest[opponent]));
+                return Some(pairs);
+            }
+        }
+
+        None
+    }
+
+    /// Indices of `teams` by closest points, then by closest SR to `home`.
+    fn opponents(home: &Team, teams: &[&Team], points_of: &dyn Fn(&Team) -> f32) -> Vec<usize> {
+        let home_points = points_of(home);
+        let key = |team: &Team| {
+            (
+                (home_points - points_of(team)).abs(),
+                (home.avg_sr - team.avg_sr).abs(),
+            )
+        };
+
+        let mut opponents: Vec<usize> = (0..teams.len()).collect();
+        opponents.sort_by(|&a, &b| {
+            key(teams[a])
+                .partial_cmp(&key(teams[b]))
+                .unwrap_or(Ordering::Equal)
+        });
+        opponents
+    }
+
+    fn elimination(teams: &Teams, double: bool) -> Bracket {
+        let seeds = teams.seeded();
+        let size = seeds.len().max(2).next_power_of_two();
+        let mut pairings = Vec::default();
+
+        for (high, low) in Schedule::seed_order(size)
+            .chunks(2)
+            .map(|pair| (pair[0], pair[1]))
+        {
+            match (seeds.get(high), seeds.get(low)) {
+                (Some(home), away) => pairings.push(Pairing::new(home, away.copied())),
+                (None, Some(home)) => pairings.push(Pairing::new(home, None)),
+                (None, None) => {}
+            }
+        }
+
+        let first_round = Round {
+            number: 1,
+            pairings,
+            ..Round::default()
+        };
+        let (losers_rounds, grand_final) = if double {
+            Schedule::losers_bracket(&first_round, size)
+        } else {
+            (Vec::default(), None)
+        };
+
+        Bracket {
+            size,
+            double,
+            seeds: seeds.iter().map(|team| team.uuid.to_string()).collect(),
+            first_round,
+            losers_rounds,
+            grand_final,
+        }
+    }
+
+    /// Losers bracket of a `size` slot bracket. First round losers meet their neighbours,
+    /// then every winners round drops its losers onto the losers bracket survivors, in
+    /// reverse order every other round so teams do not meet again right away.
+    fn losers_bracket(
+        first_round: &Round,
+        size: usize,
+    ) -> (Vec<LosersRound>, Option<BracketMatch>) {
+        let winners_rounds = size.trailing_zeros() as usize;
+        // First round byes have no loser to drop down
+        let drop_down = |index: usize| {
+            first_round.pairings[index]
+                .away
+                .as_ref()
+                .map(|_| Slot::WinnersLoser { round: 1, index })
+        };
+        let survivors = |round: &LosersRound| -> Vec<Option<Slot>> {
+            round
+                .matches
+                .iter()
+                .enumerate()
+                .map(|(index, game)| {
+                    (game.home.is_some() || game.away.is_some()).then_some(Slot::LosersWinner {
+                        round: round.number,
+                        index,
+                    })
+                })
+                .collect()
+        };
+
+        let mut rounds: Vec<LosersRound> = Vec::default();
+        let first = LosersRound {
+            number: 1,
+            matches: (0..size / 4)
+                .map(|index| BracketMatch {
+                    home: drop_down(index * 2),
+                    away: drop_down(index * 2 + 1),
+                })
+                .collect(),
+        };
+
+        if !first.matches.is_empty() {
+            rounds.push(first);
+        }
+
+        for winners_round in 2..=winners_rounds {
+            let previous = survivors(rounds.last().unwrap());
+            let count = previous.len();
+            let dropped = LosersRound {
+                number: rounds.len() + 1,
+                matches: previous
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, home)| {
+                        let index = if winners_round % 2 == 0 {
+                            count - 1 - index
+                        } else {
+                            index
+                        };
+
+                        BracketMatch {
+                            home,
+                            away: Some(Slot::WinnersLoser {
+                                round: winners_round,
+                                index,
+                            }),
+                        }
+                    })
+                    .collect(),
+            };
+            rounds.push(dropped);
+
+            if winners_round < winners_rounds {
+                let previous = survivors(rounds.last().unwrap());
+                let merged = LosersRound {
+                    number: rounds.len() + 1,
+                    matches: previous
+                        .chunks(2)
+                        .map(|pair| BracketMatch {
+                            home: pair[0],
+                            away: pair[1],
+                        })
+                        .collect(),
+                };
+                rounds.push(merged);
+            }
+        }
+
+        let losers_champion = match rounds.last() {
+            Some(round) => survivors(round)[0],
+            // Two slots, the loser of the only match gets a second chance in the final
+            None => drop_down(0),
+        };
+        let grand_final = BracketMatch {
+            home: Some(Slot::WinnersWinner {
+                round: winners_rounds,
+                index: 0,
+            }),
+            away: losers_champion,
+        };
+
+        (rounds, Some(grand_final))
+    }
+
+    /// Standard bracket order of 0-based seeds, e.g. 0-7, 3-4, 1-6, 2-5 for 8 slots,
+    /// so the top seeds can only meet in the final rounds.
+    fn seed_order(size: usize) -> Vec<usize> {
+        let mut order = vec![0];
+
+        while order.len() < size {
+            let round_size = order.len() * 2;
+            order = order
+                .iter()
+                .flat_map(|&seed| vec![seed, round_size - 1 - seed])
+                .collect();
+        }
+
+        order
+    }
+}
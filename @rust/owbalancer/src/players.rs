@@ -1,16 +1,18 @@
+use crate::arena::{no_player, Reserve, NO_PLAYER};
 use crate::matchmaking::Config;
-use crate::rating_scaler::RatingScaler;
-use crate::roles::{Role, Roles, RolesFilter, SimpleRole};
+use crate::rating_scaler::{RatingScaler, Scale};
+use crate::roles::{Role, Roles, RolesFilter, SimpleRole, SubRole};
 use crate::teams::{Team, Teams};
-use crate::AdjustSr;
+use crate::{AdjustSr, Adjustment, RatingScale};
 
-use rand::rngs::OsRng;
+use indexmap::IndexMap;
 use rand::seq::SliceRandom;
+use rand::Rng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
-use std::collections::HashMap;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Identity {
     pub uuid: String,
@@ -20,12 +22,12 @@ pub struct Identity {
     pub is_full_flex: Option<bool>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 pub struct Stats {
     pub classes: Classes,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Player {
     pub identity: Identity,
@@ -33,7 +35,7 @@ pub struct Player {
     pub created_at: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct ClassType {
     pub rank: i32,
@@ -41,27 +43,45 @@ pub struct ClassType {
     pub primary: bool,
     pub secondary: bool,
     pub is_active: bool,
+    /// Uncertainty of the rank in rating points, e.g. for self-reported ranks.
+    /// Kept up to date as the Glicko-2 deviation when rated from match results.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deviation: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volatility: Option<f64>,
+    /// Rating points team strength takes off the rank, see `Players::apply_conservative`.
+    #[serde(skip)]
+    pub uncertainty: i32,
 }
 
+/// Player classes keyed by role id, in the order they were sent.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct Classes(pub IndexMap<SimpleRole, ClassType>);
+
+/// Players keyed by uuid, in the order they were sent.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default)]
+pub struct Players(pub IndexMap<String, Player>);
+
 #[derive(Serialize, Deserialize, Debug)]
-pub struct Classes {
-    pub dps: ClassType,
-    pub tank: ClassType,
-    pub support: ClassType,
+pub struct RankIssue {
+    pub uuid: String,
+    pub name: String,
+    pub role: SimpleRole,
+    pub rank: i32,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
-pub struct Players(pub HashMap<String, Player>);
-
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct Candidate {
     pub uuid: String,
     pub name: String,
     pub is_full_flex: bool,
     pub roles: Roles,
+    /// Player index in the `Arena`, `NO_PLAYER` until the arena sets it.
+    #[serde(skip, default = "no_player")]
+    pub index: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone)]
 pub struct PlayerPool(pub Vec<Candidate>);
 
 #[derive(PartialEq)]
@@ -70,61 +90,45 @@ pub enum Direction {
     DESC,
 }
 
-impl Classes {
-    pub fn get_class(&self, role: &SimpleRole) -> &ClassType {
-        match role {
-            SimpleRole::Dps => &self.dps,
-            SimpleRole::Tank => &self.tank,
-            SimpleRole::Support => &self.support,
-        }
+impl ClassType {
+    pub fn sub_role(&self, role: &SimpleRole) -> SubRole {
+        SubRole::new(role, self.primary, self.secondary)
     }
 }
 
-impl Players {
-    pub fn get_captains(&self) -> PlayerPool {
-        let players = self
-            .0
-            .iter()
-            .filter_map(|(_, player)| {
-                if player.identity.is_captain {
-                    Some(Candidate::from(player))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        PlayerPool(players)
+impl Classes {
+    pub fn get_class(&self, role: &SimpleRole) -> Option<&ClassType> {
+        self.0.get(role)
     }
 
-    pub fn get_squires(&self) -> PlayerPool {
-        let players = self
-            .0
-            .iter()
-            .filter_map(|(_, player)| {
-                if player.identity.is_squire {
-                    Some(Candidate::from(player))
-                } else {
-                    None
-                }
-            })
-            .collect();
-
-        PlayerPool(players)
+    pub fn get_class_mut(&mut self, role: &SimpleRole) -> Option<&mut ClassType> {
+        self.0.get_mut(role)
     }
+}
 
-    pub fn feed(&self, pool: &mut PlayerPool, exclude: &Vec<String>, invert: bool) {
-        for (_, player) in &self.0 {
-            let mut pass = exclude.contains(&player.identity.uuid);
-
-            if !invert {
-                pass = !pass;
-            }
+impl Players {
+    pub fn get_player(&self, uuid: &str) -> Option<&Player> {
+        self.0.get(uuid)
+    }
 
-            if pass {
-                pool.add_player(player);
+    /// Active roles whose rank falls outside of the rating scale.
+    pub fn out_of_scale(&self, scale: &RatingScale) -> Vec<RankIssue> {
+        let mut issues = Vec::default();
+
+        for player in self.0.values() {
+            for (role, class) in &player.stats.classes.0 {
+                if class.is_active && !scale.contains(class.rank) {
+                    issues.push(RankIssue {
+                        uuid: player.identity.uuid.clone(),
+                        name: player.identity.name.clone(),
+                        role: role.clone(),
+                        rank: class.rank,
+                    });
+                }
             }
         }
+
+        issues
     }
 
     pub fn adjust_sr(&mut self, adjust: AdjustSr) {
@@ -134,34 +138,55 @@ impl Players {
 
         let rs = RatingScaler::from(adjust);
 
-        for (_, player) in &mut self.0 {
-            if player.stats.classes.dps.is_active {
-                if player.stats.classes.dps.primary {
-                    player.stats.classes.dps.rank = rs.scale("dps", "primary", player.stats.classes.dps.rank);
-                } else if player.stats.classes.dps.secondary {
-                    player.stats.classes.dps.rank = rs.scale("dps", "secondary", player.stats.classes.dps.rank);
-                } else {
-                    player.stats.classes.dps.rank = rs.scale("dps", "any", player.stats.classes.dps.rank);
+        for player in self.0.values_mut() {
+            for (role, class) in player.stats.classes.0.iter_mut() {
+                if class.is_active {
+                    let specialization = class.sub_role(role).specialization();
+                    class.rank = rs.scale(role, specialization, class.rank);
                 }
             }
+        }
+    }
 
-            if player.stats.classes.tank.is_active {
-                if player.stats.classes.tank.primary {
-                    player.stats.classes.tank.rank = rs.scale("tank", "primary", player.stats.classes.tank.rank);
-                } else if player.stats.classes.tank.secondary {
-                    player.stats.classes.tank.rank = rs.scale("tank", "secondary", player.stats.classes.tank.rank);
-                } else {
-                    player.stats.classes.tank.rank = rs.scale("tank", "any", player.stats.classes.tank.rank);
+    /// Balances on `rank - factor * deviation`, so uncertain ranks are not overrated.
+    /// Ranks are kept as they are, only team strength uses the lowered rank.
+    pub fn apply_conservative(&mut self, factor: f64) {
+        for player in self.0.values_mut() {
+            for class in player.stats.classes.0.values_mut() {
+                if let Some(deviation) = class.deviation {
+                    class.uncertainty = (factor * deviation).round() as i32;
                 }
             }
+        }
+    }
+
+    pub fn get_deviation(&self, uuid: &str, role: &SimpleRole) -> f64 {
+        self.get_player(uuid)
+            .and_then(|player| player.stats.classes.get_class(role))
+            .and_then(|class| class.deviation)
+            .unwrap_or(0.0)
+    }
+
+    /// Adjusts the rank of every active role with a lower priority than the player's best.
+    /// Full flex players and roles tied with the best one are not off roles.
+    pub fn penalize_off_roles(&mut self, penalty: &Adjustment, scale: &RatingScale) {
+        let penalty = Scale::new(penalty, scale);
+
+        for player in self.0.values_mut() {
+            if player.identity.is_full_flex == Some(true) {
+                continue;
+            }
 
-            if player.stats.classes.support.is_active {
-                if player.stats.classes.support.primary {
-                    player.stats.classes.support.rank = rs.scale("support", "primary", player.stats.classes.support.rank);
-                } else if player.stats.classes.support.secondary {
-                    player.stats.classes.support.rank = rs.scale("support", "secondary", player.stats.classes.support.rank);
-                } else {
-                    player.stats.classes.support.rank = rs.scale("support", "any", player.stats.classes.support.rank);
+            let classes = &mut player.stats.classes.0;
+            let best = classes
+                .values()
+                .filter(|class| class.is_active)
+                .map(|class| class.priority)
+                .min();
+
+            for class in classes.values_mut() {
+                if class.is_active && best.is_some_and(|best| class.priority > best) {
+                    class.rank = penalty.apply(class.rank);
                 }
             }
         }
@@ -178,13 +203,24 @@ impl Candidate {
     }
 
     fn new(uuid: String, name: String, roles: Roles, is_full_flex: bool) -> Candidate {
-        Candidate { uuid, name, roles, is_full_flex }
+        Candidate {
+            uuid,
+            name,
+            roles,
+            is_full_flex,
+            index: NO_PLAYER,
+        }
     }
 }
 
+/// Same arena player, or the same uuid for candidates outside of an arena.
 impl PartialEq for Candidate {
     fn eq(&self, other: &Self) -> bool {
-        self.uuid == other.uuid
+        if self.index == NO_PLAYER || other.index == NO_PLAYER {
+            self.uuid == other.uuid
+        } else {
+            self.index == other.index
+        }
     }
 }
 
@@ -201,7 +237,7 @@ impl From<&Player> for Candidate {
 
 impl PlayerPool {
     pub fn sort_by_rank(&mut self, direction: Direction) {
-        self.0.sort_by(|a, b| {          
+        self.0.sort_by(|a, b| {
             let ordering = b.roles.get_primary_rank().cmp(&a.roles.get_primary_rank());
 
             if direction == Direction::DESC {
@@ -230,10 +266,6 @@ impl PlayerPool {
         self.0.len()
     }
 
-    pub fn add_player(&mut self, player: &Player) {
-        self.0.push(Candidate::from(player));
-    }
-
     pub fn add_candidate(&mut self, candidate: &Candidate) {
         self.0.push(candidate.clone());
     }
@@ -260,22 +292,21 @@ impl PlayerPool {
     }
 
     pub fn dispose_of(&mut self, candidates: Vec<usize>) {
-        self.0 = self
-            .0
-            .iter_mut()
-            .enumerate()
-            .filter_map(|(id, candidate)| {
-                if candidates.contains(&id) {
-                    None
-                } else {
-                    Some(candidate.clone())
-                }
-            })
-            .collect()
+        let mut disposed = vec![false; self.0.len()];
+
+        for position in candidates {
+            disposed[position] = true;
+        }
+
+        let mut position = 0;
+        self.0.retain(|_| {
+            position += 1;
+            !disposed[position - 1]
+        });
     }
 
-    pub fn shuffle(&mut self) {
-        self.0.shuffle(&mut OsRng::default());
+    pub fn shuffle(&mut self, rng: &mut impl Rng) {
+        self.0.shuffle(rng);
     }
 
     pub fn distribute_leutenant(
@@ -287,14 +318,14 @@ impl PlayerPool {
         if offset >= self.0.len() {
             return offset;
         }
-        let candidate = self.0.get(offset).unwrap().clone();
+        let candidate = &self.0[offset];
 
-        if let Some(team) = teams.find_mate(&candidate, 2, config) {
-            return self.add_player_to_team(team, &candidate, offset);
+        if let Some(team) = teams.find_mate(candidate, 2, config) {
+            return self.add_player_to_team(team, offset);
         }
 
-        if let Some(team) = teams.find_team(2, candidate.get_primary_role(), config) {
-            return self.add_player_to_team(team, &candidate, offset);
+        if let Some(team) = teams.find_team(2, candidate, config) {
+            return self.add_player_to_team(team, offset);
         }
 
         self.distribute_leutenant(teams, offset + 1, config)
@@ -309,18 +340,18 @@ impl PlayerPool {
         if offset >= self.0.len() {
             return offset;
         }
-        let candidate = self.0.get(offset).unwrap().clone();
+        let candidate = &self.0[offset];
 
-        if let Some(team) = teams.find_perfect_ensign(&candidate, config) {
-            return self.add_player_to_team(team, &candidate, offset);
+        if let Some(team) = teams.find_perfect_ensign(candidate, config) {
+            return self.add_player_to_team(team, offset);
         }
 
-        if let Some(team) = teams.find_mate(&candidate, 3, config) {
-            return self.add_player_to_team(team, &candidate, offset);
+        if let Some(team) = teams.find_mate(candidate, 3, config) {
+            return self.add_player_to_team(team, offset);
         }
 
-        if let Some(team) = teams.find_team(3, candidate.get_primary_role(), config) {
-            return self.add_player_to_team(team, &candidate, offset);
+        if let Some(team) = teams.find_team(3, candidate, config) {
+            return self.add_player_to_team(team, offset);
         }
 
         self.distribute_ensign(teams, offset + 1, config)
@@ -328,22 +359,20 @@ impl PlayerPool {
 
     pub fn distribute_filler(&mut self, team: &mut Team, config: &Config) {
         let range = team.get_range(config);
-        let clonned = self.clone();
-        let find_candidate = clonned.filter_range(range, team, config);
 
-        if let Some(candidate) = find_candidate {
-            team.add_primary_player(candidate);
-            self.remove_candidate(candidate);
-        } else {
-            for candidate in &clonned.0 {
-                for i in 1..candidate.roles_count() {
-                    let role = candidate.roles.get(i);
-
-                    if role.is_in_range(range) && role.fits_team(team, config) {
-                        team.add_player(candidate, role);
-                        self.remove_candidate(candidate);
-                        return;
-                    }
+        if let Some(position) = self.filter_range(range, team, config) {
+            team.add_primary_player(&self.0.remove(position));
+            return;
+        }
+
+        for (position, candidate) in self.0.iter().enumerate() {
+            for i in 1..candidate.roles_count() {
+                let role = candidate.roles.get(i);
+
+                if role.is_in_range(range, config) && role.fits_team(team, config) {
+                    team.add_player(candidate, role);
+                    self.0.remove(position);
+                    return;
                 }
             }
         }
@@ -354,12 +383,12 @@ impl PlayerPool {
         role: SimpleRole,
         range: (i32, i32),
         teams: &Teams,
-        db: &PlayerPool,
+        reserve: &Reserve,
         target_team: &Team,
         config: &Config,
     ) -> Option<(usize, usize, &Candidate)> {
         for leftover in &self.0 {
-            let lost = teams.replace_leftover(leftover, &role, range, db, target_team, config);
+            let lost = teams.replace_leftover(leftover, &role, range, reserve, target_team, config);
             if let Some(replacement) = lost {
                 return Some((replacement.0, replacement.1, leftover));
             }
@@ -368,11 +397,11 @@ impl PlayerPool {
         None
     }
 
-    pub fn get_primary_average(&self, teams_sr: i32, teams_count: usize) -> i32 {
+    pub fn get_primary_average(&self, teams_sr: i32, teams_count: usize, config: &Config) -> i32 {
         let sum: i32 = self
             .0
             .iter()
-            .map(|candidate| candidate.get_primary_role().decompose().1)
+            .map(|candidate| candidate.get_primary_role().strength(config))
             .sum();
         let count = self.0.len();
 
@@ -382,59 +411,39 @@ impl PlayerPool {
         (mutual_sum as f32 / mutual_count as f32).floor() as i32
     }
 
-    pub fn get_by_id(&self, uuid: String) -> Option<&Candidate> {
-        self.0.iter().find(|&candidate| candidate.uuid == uuid)
-    }
-
-    fn remove_candidate(&mut self, candidate: &Candidate) {
-        let find = self
+    /// Position of the best primary role candidate within `range`.
+    fn filter_range(&self, range: (i32, i32), team: &Team, config: &Config) -> Option<usize> {
+        let mut candidates: Vec<(usize, &Candidate)> = self
             .0
             .iter()
-            .position(|stored_candidate| stored_candidate == candidate);
-
-        if let Some(index) = find {
-            self.0.remove(index);
-        }
-    }
-
-    fn filter_range(&self, range: (i32, i32), team: &Team, config: &Config) -> Option<&Candidate> {
-        let mut candidates: Vec<&Candidate> = self
-            .0
-            .iter()
-            .filter(|&candidate| {
+            .enumerate()
+            .filter(|(_, candidate)| {
                 let role = candidate.get_primary_role();
-                role.is_in_range(range) && role.fits_team(team, config)
+                role.is_in_range(range, config) && role.fits_team(team, config)
             })
             .collect();
 
-        candidates.sort_by(|a, b| {
-            let role1 = a.get_primary_role().decompose();
-            let role2 = b.get_primary_role().decompose();
+        candidates.sort_by(|(_, a), (_, b)| {
+            let strength1 = a.get_primary_role().strength(config);
+            let strength2 = b.get_primary_role().strength(config);
             let new_count = team.members_count() as i32 + 1;
-            let new_sr1 = (team.total_sr + role1.1) / new_count;
-            let new_sr2 = (team.total_sr + role2.1) / new_count;
+            let new_sr1 = (team.total_sr + strength1) / new_count;
+            let new_sr2 = (team.total_sr + strength2) / new_count;
 
             let disp1 = (config.players_average - new_sr1).abs();
             let disp2 = (config.players_average - new_sr2).abs();
+            let repeats1 = config.arena.repeats_with(team, a.index);
+            let repeats2 = config.arena.repeats_with(team, b.index);
 
-            disp1.cmp(&disp2)
+            // Every candidate is within range, so new teammates come first
+            repeats1.cmp(&repeats2).then(disp1.cmp(&disp2))
         });
 
-        if let Some(&a) = candidates.get(0) {
-            Some(a)
-        } else {
-            None
-        }
+        candidates.first().map(|(position, _)| *position)
     }
 
-    fn add_player_to_team(
-        &mut self,
-        team: &mut Team,
-        candidate: &Candidate,
-        offset: usize,
-    ) -> usize {
-        team.add_primary_player(candidate);
-        self.0.remove(offset);
+    fn add_player_to_team(&mut self, team: &mut Team, offset: usize) -> usize {
+        team.add_primary_player(&self.0.remove(offset));
 
         offset
     }
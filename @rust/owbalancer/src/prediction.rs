@@ -0,0 +1,64 @@
+use crate::teams::{Team, Teams};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Logistic model over the role weighted team average: a gap of `scale` rating
+/// points means ten to one odds. Scales below one point are taken as one.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[serde(default)]
+pub struct Predictor {
+    pub scale: f64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Predictions {
+    /// `matrix[i][j]` is the chance of team `i` beating team `j`, in result team order.
+    pub matrix: Vec<Vec<f64>>,
+    /// Mean chance of every team to beat any other team of the lobby.
+    pub vs_field: Vec<f64>,
+}
+
+impl Default for Predictor {
+    fn default() -> Self {
+        Predictor { scale: 400.0 }
+    }
+}
+
+impl Predictor {
+    pub fn win_probability(&self, team: &Team, opponent: &Team) -> f64 {
+        let gap = (opponent.avg_sr - team.avg_sr) as f64;
+
+        1.0 / (1.0 + 10f64.powf(gap / self.scale.max(1.0)))
+    }
+
+    pub fn predict(&self, teams: &Teams) -> Predictions {
+        let matrix: Vec<Vec<f64>> = teams
+            .0
+            .iter()
+            .map(|team| {
+                teams
+                    .0
+                    .iter()
+                    .map(|opponent| self.win_probability(team, opponent))
+                    .collect()
+            })
+            .collect();
+
+        let opponents = teams.0.len().saturating_sub(1).max(1) as f64;
+        let vs_field = matrix
+            .iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .map(|(_, chance)| chance)
+                    .sum::<f64>()
+                    / opponents
+            })
+            .collect();
+
+        Predictions { matrix, vs_field }
+    }
+}
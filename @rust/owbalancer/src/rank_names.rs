@@ -0,0 +1,206 @@
+use serde::{Deserialize, Serialize};
+
+/// Tier of a rank system. Tiers with a single division are named without a number.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Tier {
+    pub name: String,
+    /// Lowest rating of the tier.
+    pub start: i32,
+    pub divisions: u8,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+/// Tiers ordered from the lowest, `top` is the end of the last tier.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RankSystem {
+    pub tiers: Vec<Tier>,
+    pub top: i32,
+}
+
+/// Preset name (`current` or `legacy`) or a custom system.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum RankSystemOption {
+    Preset(String),
+    Custom(RankSystem),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RankName {
+    pub tier: String,
+    /// `None` for tiers without divisions. Division 1 is the highest.
+    pub division: Option<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RankError {
+    UnknownTier { tier: String },
+    InvalidDivision { division: String },
+    UnknownPreset { name: String },
+}
+
+impl Tier {
+    fn new(name: &str, start: i32, divisions: u8, aliases: &[&str]) -> Tier {
+        Tier {
+            name: name.to_string(),
+            start,
+            divisions,
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+        }
+    }
+
+    /// `name` is lowercase, tier names and aliases match in any case.
+    fn matches(&self, name: &str) -> bool {
+        self.name.to_lowercase() == name
+            || self
+                .aliases
+                .iter()
+                .any(|alias| alias.to_lowercase() == name)
+    }
+}
+
+impl Default for RankSystemOption {
+    fn default() -> Self {
+        RankSystemOption::Preset(String::from("current"))
+    }
+}
+
+impl RankSystemOption {
+    pub fn system(&self) -> Result<RankSystem, RankError> {
+        match self {
+            RankSystemOption::Preset(name) => match name.as_str() {
+                "current" => Ok(RankSystem::current()),
+                "legacy" => Ok(RankSystem::legacy()),
+                _ => Err(RankError::UnknownPreset { name: name.clone() }),
+            },
+            RankSystemOption::Custom(system) => Ok(system.clone()),
+        }
+    }
+}
+
+impl RankSystem {
+    /// Bronze to Champion with five divisions each, spread over 0-2000.
+    pub fn current() -> RankSystem {
+        RankSystem {
+            tiers: vec![
+                Tier::new("Bronze", 0, 5, &[]),
+                Tier::new("Silver", 250, 5, &[]),
+                Tier::new("Gold", 500, 5, &[]),
+                Tier::new("Platinum", 750, 5, &["plat"]),
+                Tier::new("Diamond", 1000, 5, &["dia"]),
+                Tier::new("Master", 1250, 5, &["masters"]),
+                Tier::new("Grandmaster", 1500, 5, &["gm", "grandmasters"]),
+                Tier::new("Champion", 1750, 5, &["champ", "champions"]),
+            ],
+            top: 2000,
+        }
+    }
+
+    /// Skill rating tiers before divisions, 0-5000 SR scaled to 0-2000.
+    pub fn legacy() -> RankSystem {
+        RankSystem {
+            tiers: vec![
+                Tier::new("Bronze", 0, 1, &[]),
+                Tier::new("Silver", 600, 1, &[]),
+                Tier::new("Gold", 800, 1, &[]),
+                Tier::new("Platinum", 1000, 1, &["plat"]),
+                Tier::new("Diamond", 1200, 1, &["dia"]),
+                Tier::new("Master", 1400, 1, &["masters"]),
+                Tier::new("Grandmaster", 1600, 1, &["gm", "grandmasters"]),
+            ],
+            top: 2000,
+        }
+    }
+
+    fn end(&self, index: usize) -> i32 {
+        self.tiers
+            .get(index + 1)
+            .map_or(self.top, |tier| tier.start)
+    }
+
+    fn division_width(&self, index: usize) -> i32 {
+        let tier = &self.tiers[index];
+
+        (self.end(index) - tier.start) / tier.divisions.max(1) as i32
+    }
+
+    /// Lowest rating of a rank such as `Diamond 3`, `masters1` or `GM`. Plain numbers
+    /// are returned as they are, a missing division means the lowest one.
+    pub fn parse(&self, rank: &str) -> Result<i32, RankError> {
+        let rank = rank.trim();
+
+        if let Ok(rating) = rank.parse::<i32>() {
+            return Ok(rating);
+        }
+
+        let split = rank
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rank.len());
+        let (name, division) = rank.split_at(split);
+        let name = name.trim().to_lowercase();
+        let division = division.trim();
+
+        let index = self
+            .tiers
+            .iter()
+            .position(|tier| tier.matches(&name))
+            .ok_or_else(|| RankError::UnknownTier {
+                tier: name.to_string(),
+            })?;
+        let tier = &self.tiers[index];
+
+        let division = if division.is_empty() {
+            tier.divisions.max(1)
+        } else {
+            division
+                .parse::<u8>()
+                .ok()
+                .filter(|&value| value >= 1 && value <= tier.divisions.max(1))
+                .ok_or_else(|| RankError::InvalidDivision {
+                    division: division.to_string(),
+                })?
+        };
+
+        let steps = (tier.divisions.max(1) - division) as i32;
+
+        Ok(tier.start + steps * self.division_width(index))
+    }
+
+    /// Tier and division the rating falls into, ratings outside the system are clamped.
+    pub fn name(&self, rating: i32) -> Option<RankName> {
+        let index = self
+            .tiers
+            .iter()
+            .rposition(|tier| tier.start <= rating)
+            .unwrap_or(0);
+        let tier = self.tiers.get(index)?;
+
+        if tier.divisions <= 1 {
+            return Some(RankName {
+                tier: tier.name.clone(),
+                division: None,
+            });
+        }
+
+        let width = self.division_width(index).max(1);
+        let steps = ((rating - tier.start).max(0) / width).min(tier.divisions as i32 - 1);
+
+        Some(RankName {
+            tier: tier.name.clone(),
+            division: Some(tier.divisions - steps as u8),
+        })
+    }
+}
+
+impl std::fmt::Display for RankName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.division {
+            Some(division) => write!(f, "{} {}", self.tier, division),
+            None => write!(f, "{}", self.tier),
+        }
+    }
+}
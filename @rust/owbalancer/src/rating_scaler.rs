@@ -1,52 +1,150 @@
 use flo_curves::bezier;
 use flo_curves::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
-use crate::{AdjustSr, BezierPoint, SpecializationPoints};
+use crate::roles::SimpleRole;
+use crate::{AdjustSr, Adjustment, BezierPoint, Point, RatingScale, SpecializationPoints};
 
-const MAX_ADJUSTMENT: f64 = 50.0;
-const TOP_RATING: f64 = 2000.0;
+pub const SPECIALIZATIONS: [&str; 3] = ["any", "primary", "secondary"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum CurveError {
+    TooFewPoints {
+        count: usize,
+    },
+    NotOrdered {
+        index: usize,
+    },
+    StartsAboveZero {
+        x: f64,
+    },
+    EndsBelowTop {
+        x: f64,
+    },
+    NotMonotonic {
+        index: usize,
+    },
+    /// Scale top has to be a positive rating, points are shares of it.
+    InvalidTopRating {
+        top_rating: f64,
+    },
+    /// Negative maximum adjustments would turn every curve upside down.
+    InvalidMaxAdjustment {
+        max_adjustment: f64,
+    },
+}
+
+/// Issues without a role belong to the rating scale, or to the off-role penalty when
+/// their specialization is `offRole`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurveIssue {
+    pub role: Option<SimpleRole>,
+    pub specialization: Option<String>,
+    pub error: CurveError,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurveSample {
+    pub rating: i32,
+    pub adjusted: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurvePreview {
+    pub role: SimpleRole,
+    pub specialization: String,
+    pub samples: Vec<CurveSample>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct AdjustPreview {
+    pub issues: Vec<CurveIssue>,
+    pub curves: Vec<CurvePreview>,
+}
+
+/// Single rating adjustment ready to be applied.
+pub enum Scale {
+    Bezier(Vec<(i32, bezier::Curve<Coord2>)>),
+    Linear(Vec<(i32, f64)>),
+    Offset(i32),
+    Percent(f64),
+}
 
 pub struct SpecializationScaler {
-    any: Vec<(i32, bezier::Curve<Coord2>)>,
-    primary: Vec<(i32, bezier::Curve<Coord2>)>,
-    secondary: Vec<(i32, bezier::Curve<Coord2>)>,
+    any: Scale,
+    primary: Scale,
+    secondary: Scale,
+}
+
+impl Scale {
+    pub fn new(adjustment: &Adjustment, scale: &RatingScale) -> Self {
+        match adjustment {
+            Adjustment::Bezier(points) => {
+                Scale::Bezier(RatingScaler::from_bezier_points(points, scale))
+            }
+            Adjustment::Linear { linear } => {
+                Scale::Linear(RatingScaler::from_linear_points(linear, scale))
+            }
+            Adjustment::Offset { offset } => Scale::Offset(*offset),
+            Adjustment::Percent { percent } => Scale::Percent(*percent),
+        }
+    }
+
+    pub fn apply(&self, rating: i32) -> i32 {
+        match self {
+            Scale::Bezier(curves) => RatingScaler::scale_bezier(rating, curves),
+            Scale::Linear(table) => RatingScaler::scale_linear(rating, table),
+            Scale::Offset(offset) => rating + offset,
+            Scale::Percent(percent) => (rating as f64 * (1.0 + percent / 100.0)).floor() as i32,
+        }
+    }
 }
 
 impl SpecializationScaler {
-    fn new(data: SpecializationPoints) -> Self {
+    fn new(data: &SpecializationPoints, scale: &RatingScale) -> Self {
         Self {
-            any: RatingScaler::from_bezier_points(data.any),
-            primary: RatingScaler::from_bezier_points(data.primary),
-            secondary: RatingScaler::from_bezier_points(data.secondary),
+            any: Scale::new(&data.any, scale),
+            primary: Scale::new(&data.primary, scale),
+            secondary: Scale::new(&data.secondary, scale),
         }
     }
 
-    fn get(&self, specialization: &str) -> Option<&Vec<(i32, bezier::Curve<Coord2>)>> {
+    fn get(&self, specialization: &str) -> Option<&Scale> {
         match specialization {
             "any" => Some(&self.any),
             "primary" => Some(&self.primary),
             "secondary" => Some(&self.secondary),
-            _ => None
+            _ => None,
         }
     }
 }
 
 pub struct RatingScaler {
-    dps: SpecializationScaler,
-    tank: SpecializationScaler,
-    support: SpecializationScaler,
+    roles: HashMap<SimpleRole, SpecializationScaler>,
 }
 
 impl RatingScaler {
-    fn scale_role(&self, rating: i32, curves: &Vec<(i32, bezier::Curve<Coord2>)>) -> i32 {
+    fn scale_bezier(rating: i32, curves: &[(i32, bezier::Curve<Coord2>)]) -> i32 {
+        if curves.is_empty() {
+            return rating;
+        }
+
         let mut curve_index = 0;
 
-        for i in 0..curves.len() {
-            if curves[i].0 < rating {
+        for (i, (max_rating, _)) in curves.iter().enumerate() {
+            if *max_rating < rating {
                 curve_index = i + 1
             }
         }
 
+        // Ratings past the last point are scaled by the last segment
+        let curve_index = curve_index.min(curves.len() - 1);
+
         let curve = &curves[curve_index].1;
         let mut min_rating = 0;
         let max_rating = curves[curve_index].0;
@@ -55,24 +153,227 @@ impl RatingScaler {
             min_rating = curves[curve_index - 1].0;
         }
 
-        let t = (rating as f64 - min_rating as f64) / (max_rating as f64 - min_rating as f64);
+        // Points sharing x leave a segment without width, it is scaled by its end
+        let t = if max_rating > min_rating {
+            (rating as f64 - min_rating as f64) / (max_rating as f64 - min_rating as f64)
+        } else {
+            1.0
+        };
+        let t = t.clamp(0.0, 1.0);
         let pos = curve.point_at_pos(t);
         let scale = 1.0 + pos.y() / 100.0;
 
         (rating as f64 * scale).floor() as i32
     }
 
-    pub fn scale(&self, role: &str, specialization: &str, rating: i32) -> i32 {
-        match role {
-            "dps" => self.scale_role(rating, self.dps.get(specialization).unwrap()),
-            "tank" => self.scale_role(rating, self.tank.get(specialization).unwrap()),
-            "support" => self.scale_role(rating, self.support.get(specialization).unwrap()),
-            _ => 0,
+    fn scale_linear(rating: i32, table: &[(i32, f64)]) -> i32 {
+        if table.is_empty() {
+            return rating;
+        }
+
+        let first = table.first().unwrap();
+        let last = table.last().unwrap();
+
+        let adjustment = if rating <= first.0 {
+            first.1
+        } else if rating >= last.0 {
+            last.1
+        } else {
+            let upper = table.iter().position(|(x, _)| *x >= rating).unwrap();
+            let (x1, y1) = table[upper - 1];
+            let (x2, y2) = table[upper];
+            let t = (rating - x1) as f64 / (x2 - x1) as f64;
+
+            y1 + (y2 - y1) * t
+        };
+
+        let scale = 1.0 + adjustment / 100.0;
+
+        (rating as f64 * scale).floor() as i32
+    }
+
+    /// Roles without an adjustment keep their rating.
+    pub fn scale(&self, role: &SimpleRole, specialization: &str, rating: i32) -> i32 {
+        match self.roles.get(role) {
+            Some(scaler) => scaler.get(specialization).unwrap().apply(rating),
+            None => rating,
+        }
+    }
+
+    /// Samples every role and specialization curve each `step` rating points,
+    /// so the editor can show the exact ratings the balancer will use.
+    pub fn preview(adjust: &AdjustSr, step: i32) -> AdjustPreview {
+        let scaler = RatingScaler::from(adjust);
+        let step = step.max(1) as usize;
+        let mut curves = Vec::default();
+
+        for role in adjust.role_ids() {
+            for specialization in SPECIALIZATIONS.iter() {
+                let samples = (0..=adjust.scale.top_rating as i32)
+                    .step_by(step)
+                    .map(|rating| CurveSample {
+                        rating,
+                        adjusted: scaler.scale(role, specialization, rating),
+                    })
+                    .collect();
+
+                curves.push(CurvePreview {
+                    role: role.clone(),
+                    specialization: specialization.to_string(),
+                    samples,
+                });
+            }
+        }
+
+        AdjustPreview {
+            issues: RatingScaler::validate(adjust),
+            curves,
+        }
+    }
+
+    /// Reports curves that are unordered, fold back on themselves or do not cover
+    /// the whole rating range, and a scale curves cannot be normalized against.
+    pub fn validate(adjust: &AdjustSr) -> Vec<CurveIssue> {
+        let mut issues: Vec<CurveIssue> = RatingScaler::validate_scale(&adjust.scale)
+            .into_iter()
+            .map(|error| CurveIssue {
+                role: None,
+                specialization: None,
+                error,
+            })
+            .collect();
+
+        for role in adjust.role_ids() {
+            for specialization in SPECIALIZATIONS.iter() {
+                let adjustment = adjust.get(role).unwrap().get(specialization).unwrap();
+
+                for error in RatingScaler::validate_adjustment(adjustment) {
+                    issues.push(CurveIssue {
+                        role: Some(role.clone()),
+                        specialization: Some(specialization.to_string()),
+                        error,
+                    });
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// Reports an off-role penalty curve like `validate` reports role curves.
+    pub fn validate_penalty(penalty: &Adjustment, scale: &RatingScale) -> Vec<CurveIssue> {
+        let scale_issues = RatingScaler::validate_scale(scale)
+            .into_iter()
+            .map(|error| CurveIssue {
+                role: None,
+                specialization: None,
+                error,
+            });
+        let penalty_issues = RatingScaler::validate_adjustment(penalty)
+            .into_iter()
+            .map(|error| CurveIssue {
+                role: None,
+                specialization: Some(String::from("offRole")),
+                error,
+            });
+
+        scale_issues.chain(penalty_issues).collect()
+    }
+
+    fn validate_scale(scale: &RatingScale) -> Vec<CurveError> {
+        let mut errors = Vec::default();
+
+        if !(scale.top_rating.is_finite() && scale.top_rating > 0.0) {
+            errors.push(CurveError::InvalidTopRating {
+                top_rating: scale.top_rating,
+            });
+        }
+
+        if !(scale.max_adjustment.is_finite() && scale.max_adjustment >= 0.0) {
+            errors.push(CurveError::InvalidMaxAdjustment {
+                max_adjustment: scale.max_adjustment,
+            });
+        }
+
+        errors
+    }
+
+    fn validate_adjustment(adjustment: &Adjustment) -> Vec<CurveError> {
+        match adjustment {
+            Adjustment::Bezier(points) => RatingScaler::validate_bezier(points),
+            Adjustment::Linear { linear } => RatingScaler::validate_range(
+                &linear.iter().map(|point| point.x).collect::<Vec<_>>(),
+            ),
+            Adjustment::Offset { .. } | Adjustment::Percent { .. } => Vec::default(),
         }
     }
 
-    fn from_bezier_points(points: Vec<BezierPoint>) -> Vec<(i32, bezier::Curve<Coord2>)> {
+    fn validate_range(xs: &[f64]) -> Vec<CurveError> {
+        let mut errors = Vec::default();
+
+        if xs.len() < 2 {
+            errors.push(CurveError::TooFewPoints { count: xs.len() });
+            return errors;
+        }
+
+        let first = *xs.first().unwrap();
+        let last = *xs.last().unwrap();
+
+        if first > 0.0 {
+            errors.push(CurveError::StartsAboveZero { x: first });
+        }
+
+        if last < 1.0 {
+            errors.push(CurveError::EndsBelowTop { x: last });
+        }
+
+        for i in 1..xs.len() {
+            if xs[i] <= xs[i - 1] {
+                errors.push(CurveError::NotOrdered { index: i });
+            }
+        }
+
+        errors
+    }
+
+    fn validate_bezier(points: &[BezierPoint]) -> Vec<CurveError> {
+        let mut errors = RatingScaler::validate_range(
+            &points
+                .iter()
+                .map(|point| point.position.x)
+                .collect::<Vec<_>>(),
+        );
+
+        for i in 1..points.len() {
+            let previous = &points[i - 1];
+            let current = &points[i];
+
+            if current.position.x <= previous.position.x {
+                continue;
+            }
+
+            // Control polygon has to move forward on x, otherwise the segment folds back
+            let control_start = previous.position.x + previous.control.x;
+            let control_end = current.position.x - current.control.x;
+
+            if control_start < previous.position.x
+                || control_end < control_start
+                || current.position.x < control_end
+            {
+                errors.push(CurveError::NotMonotonic { index: i });
+            }
+        }
+
+        errors
+    }
+
+    fn from_bezier_points(
+        points: &[BezierPoint],
+        scale: &RatingScale,
+    ) -> Vec<(i32, bezier::Curve<Coord2>)> {
         let mut curves = Vec::default();
+        let top_rating = scale.top_rating;
+        let max_adjustment = scale.max_adjustment;
 
         for i in 1..points.len() {
             let previous = &points[i - 1];
@@ -80,40 +381,65 @@ impl RatingScaler {
 
             let curve = bezier::Curve::from_points(
                 Coord2(
-                    previous.position.x * TOP_RATING,
-                    previous.position.y * MAX_ADJUSTMENT,
+                    previous.position.x * top_rating,
+                    previous.position.y * max_adjustment,
                 ),
                 (
                     Coord2(
-                        previous.position.x * TOP_RATING + previous.control.x * TOP_RATING,
-                        previous.position.y * MAX_ADJUSTMENT
-                            + (previous.control.y - 1.0) * MAX_ADJUSTMENT,
+                        previous.position.x * top_rating + previous.control.x * top_rating,
+                        previous.position.y * max_adjustment
+                            + (previous.control.y - 1.0) * max_adjustment,
                     ),
                     Coord2(
-                        current.position.x * TOP_RATING - current.control.x * TOP_RATING,
-                        current.position.y * MAX_ADJUSTMENT
-                            - (current.control.y - 1.0) * MAX_ADJUSTMENT,
+                        current.position.x * top_rating - current.control.x * top_rating,
+                        current.position.y * max_adjustment
+                            - (current.control.y - 1.0) * max_adjustment,
                     ),
                 ),
                 Coord2(
-                    current.position.x * TOP_RATING,
-                    current.position.y * MAX_ADJUSTMENT,
+                    current.position.x * top_rating,
+                    current.position.y * max_adjustment,
                 ),
             );
 
-            curves.push(((current.position.x * TOP_RATING).floor() as i32, curve))
+            curves.push(((current.position.x * top_rating).floor() as i32, curve))
         }
 
         curves
     }
+
+    fn from_linear_points(points: &[Point], scale: &RatingScale) -> Vec<(i32, f64)> {
+        points
+            .iter()
+            .map(|point| {
+                (
+                    (point.x * scale.top_rating).floor() as i32,
+                    point.y * scale.max_adjustment,
+                )
+            })
+            .collect()
+    }
+}
+
+impl From<&AdjustSr> for RatingScaler {
+    fn from(adjust: &AdjustSr) -> Self {
+        let roles = adjust
+            .roles
+            .iter()
+            .map(|(role, points)| {
+                (
+                    role.clone(),
+                    SpecializationScaler::new(points, &adjust.scale),
+                )
+            })
+            .collect();
+
+        Self { roles }
+    }
 }
 
 impl From<AdjustSr> for RatingScaler {
     fn from(adjust: AdjustSr) -> Self {
-        Self {
-            dps: SpecializationScaler::new(adjust.dps),
-            tank: SpecializationScaler::new(adjust.tank),
-            support: SpecializationScaler::new(adjust.support),
-        }
+        Self::from(&adjust)
     }
 }
@@ -0,0 +1,387 @@
+use crate::players::{ClassType, Players};
+use crate::teams::Team;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Glicko-2 internal scale for the default 400 point logistic scale.
+const GLICKO_SCALE: f64 = 173.7178;
+
+/// Expected scores are kept this far from 0 and 1.
+const MIN_EXPECTED: f64 = 1e-6;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchResult {
+    pub home: Team,
+    pub away: Team,
+    pub home_score: f64,
+    pub away_score: f64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "model", rename_all = "camelCase")]
+pub enum RatingModel {
+    #[serde(rename_all = "camelCase")]
+    Elo {
+        k_factor: f64,
+        #[serde(default = "RatingModel::default_scale")]
+        scale: f64,
+    },
+    /// Every match is rated as its own rating period.
+    #[serde(rename_all = "camelCase")]
+    Glicko2 {
+        #[serde(default = "RatingModel::default_scale")]
+        scale: f64,
+        #[serde(default = "RatingModel::default_tau")]
+        tau: f64,
+        #[serde(default = "RatingModel::default_deviation")]
+        deviation: f64,
+        #[serde(default = "RatingModel::default_volatility")]
+        volatility: f64,
+    },
+}
+
+/// Model setting ratings cannot be computed with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum RatingError {
+    InvalidScale { scale: f64 },
+    InvalidKFactor { k_factor: f64 },
+    InvalidTau { tau: f64 },
+    InvalidDeviation { deviation: f64 },
+    InvalidVolatility { volatility: f64 },
+}
+
+/// Glicko-2 rating of a single player on the internal scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Glicko2 {
+    pub mu: f64,
+    pub phi: f64,
+    pub sigma: f64,
+}
+
+/// Game of a rating period. The expected score is taken for `own_mu`, which is the
+/// rating of the whole team when players are rated within one.
+#[derive(Debug, Clone, Copy)]
+pub struct Glicko2Game {
+    pub own_mu: f64,
+    pub opponent_mu: f64,
+    pub opponent_phi: f64,
+    pub score: f64,
+}
+
+/// Rating of one side of a match on the Glicko-2 scale.
+struct Side {
+    mu: f64,
+    phi: f64,
+}
+
+impl RatingModel {
+    fn default_scale() -> f64 {
+        400.0
+    }
+
+    fn default_tau() -> f64 {
+        0.5
+    }
+
+    fn default_deviation() -> f64 {
+        350.0
+    }
+
+    fn default_volatility() -> f64 {
+        0.06
+    }
+
+    /// Share of the match won by the home team, a draw when nothing was scored.
+    fn home_share(result: &MatchResult) -> f64 {
+        let total = result.home_score + result.away_score;
+
+        if total <= 0.0 {
+            0.5
+        } else {
+            result.home_score / total
+        }
+    }
+
+    /// Scale, deviation, volatility and tau have to be positive and the K-factor must
+    /// not be negative, otherwise ratings end up as NaN or the volatility never converges.
+    pub fn validate(&self) -> Result<(), RatingError> {
+        let positive = |value: f64| value.is_finite() && value > 0.0;
+
+        match *self {
+            RatingModel::Elo { k_factor, scale } => {
+                if !positive(scale) {
+                    return Err(RatingError::InvalidScale { scale });
+                }
+
+                if !(k_factor.is_finite() && k_factor >= 0.0) {
+                    return Err(RatingError::InvalidKFactor { k_factor });
+                }
+            }
+            RatingModel::Glicko2 {
+                scale,
+                tau,
+                deviation,
+                volatility,
+            } => {
+                if !positive(scale) {
+                    return Err(RatingError::InvalidScale { scale });
+                }
+
+                if !positive(tau) {
+                    return Err(RatingError::InvalidTau { tau });
+                }
+
+                if !positive(deviation) {
+                    return Err(RatingError::InvalidDeviation { deviation });
+                }
+
+                if !positive(volatility) {
+                    return Err(RatingError::InvalidVolatility { volatility });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Updates the rating of every member on the role they played. Matches are
+    /// applied in order, members missing from `players` are skipped. Nothing is
+    /// updated for an invalid model, see `validate`.
+    pub fn update(
+        &self,
+        players: &mut Players,
+        matches: &[MatchResult],
+    ) -> Result<(), RatingError> {
+        self.validate()?;
+
+        for result in matches {
+            let share = RatingModel::home_share(result);
+
+            match self {
+                RatingModel::Elo { k_factor, scale } => {
+                    let home = RatingModel::average_rank(players, &result.home);
+                    let away = RatingModel::average_rank(players, &result.away);
+                    let expected = 1.0 / (1.0 + 10f64.powf((away - home) / scale));
+                    let delta = k_factor * (share - expected);
+
+                    RatingModel::apply_elo(players, &result.home, delta);
+                    RatingModel::apply_elo(players, &result.away, -delta);
+                }
+                RatingModel::Glicko2 { scale, .. } => {
+                    let factor = scale / 400.0 * GLICKO_SCALE;
+                    let home = self.side(players, &result.home, factor);
+                    let away = self.side(players, &result.away, factor);
+
+                    self.apply_glicko(players, &result.home, (&home, &away), share, factor);
+                    self.apply_glicko(players, &result.away, (&away, &home), 1.0 - share, factor);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn members<'a>(
+        players: &'a mut Players,
+        team: &'a Team,
+    ) -> impl Iterator<Item = &'a mut ClassType> + 'a {
+        let mut classes = Vec::default();
+
+        for (uuid, player) in players.0.iter_mut() {
+            if let Some(member) = team.members.iter().find(|member| member.uuid == *uuid) {
+                if let Some(class) = player.stats.classes.get_class_mut(&member.role) {
+                    classes.push(class);
+                }
+            }
+        }
+
+        classes.into_iter()
+    }
+
+    fn average_rank(players: &mut Players, team: &Team) -> f64 {
+        let ranks: Vec<f64> = RatingModel::members(players, team)
+            .map(|class| class.rank as f64)
+            .collect();
+
+        if ranks.is_empty() {
+            return 0.0;
+        }
+
+        ranks.iter().sum::<f64>() / ranks.len() as f64
+    }
+
+    fn apply_elo(players: &mut Players, team: &Team, delta: f64) {
+        for class in RatingModel::members(players, team) {
+            class.rank = (class.rank as f64 + delta).round() as i32;
+        }
+    }
+
+    fn deviation(&self, class: &ClassType) -> f64 {
+        match self {
+            RatingModel::Glicko2 { deviation, .. } => class.deviation.unwrap_or(*deviation),
+            RatingModel::Elo { .. } => 0.0,
+        }
+    }
+
+    fn volatility(&self, class: &ClassType) -> f64 {
+        match self {
+            RatingModel::Glicko2 { volatility, .. } => class.volatility.unwrap_or(*volatility),
+            RatingModel::Elo { .. } => 0.0,
+        }
+    }
+
+    fn tau(&self) -> f64 {
+        match self {
+            RatingModel::Glicko2 { tau, .. } => *tau,
+            RatingModel::Elo { .. } => 0.0,
+        }
+    }
+
+    /// Team as a single opponent: mean rating and root mean square deviation.
+    fn side(&self, players: &mut Players, team: &Team, factor: f64) -> Side {
+        let ratings: Vec<(f64, f64)> = RatingModel::members(players, team)
+            .map(|class| (class.rank as f64, self.deviation(class)))
+            .collect();
+
+        if ratings.is_empty() {
+            return Side {
+                mu: 0.0,
+                phi: self.default_phi(factor),
+            };
+        }
+
+        let count = ratings.len() as f64;
+        let rank = ratings.iter().map(|(rank, _)| rank).sum::<f64>() / count;
+        let deviation = (ratings.iter().map(|(_, rd)| rd * rd).sum::<f64>() / count).sqrt();
+
+        Side {
+            mu: rank / factor,
+            phi: deviation / factor,
+        }
+    }
+
+    fn default_phi(&self, factor: f64) -> f64 {
+        match self {
+            RatingModel::Glicko2 { deviation, .. } => deviation / factor,
+            RatingModel::Elo { .. } => 0.0,
+        }
+    }
+
+    fn apply_glicko(
+        &self,
+        players: &mut Players,
+        team: &Team,
+        (own, opponent): (&Side, &Side),
+        score: f64,
+        factor: f64,
+    ) {
+        let tau = self.tau();
+
+        for class in RatingModel::members(players, team) {
+            let rating = Glicko2 {
+                mu: class.rank as f64 / factor,
+                phi: self.deviation(class) / factor,
+                sigma: self.volatility(class),
+            };
+            // Expectation of the whole team, so members are not rated against teammates
+            let game = Glicko2Game {
+                own_mu: own.mu,
+                opponent_mu: opponent.mu,
+                opponent_phi: opponent.phi,
+                score,
+            };
+            let rating = rating.rate(&[game], tau);
+
+            class.rank = (rating.mu * factor).round() as i32;
+            class.deviation = Some(rating.phi * factor);
+            class.volatility = Some(rating.sigma);
+        }
+    }
+}
+
+impl Glicko2 {
+    /// Rating after the `games` of one rating period, steps 3 to 7 of the Glicko-2 paper.
+    /// `tau` has to be positive, see `RatingModel::validate`.
+    pub fn rate(self, games: &[Glicko2Game], tau: f64) -> Glicko2 {
+        if games.is_empty() {
+            return self;
+        }
+
+        let g = |phi: f64| 1.0 / (1.0 + 3.0 * phi * phi / (PI * PI)).sqrt();
+        let outcomes: Vec<(f64, f64, f64)> = games
+            .iter()
+            .map(|game| {
+                let g = g(game.opponent_phi);
+                let expected = 1.0 / (1.0 + (-g * (game.own_mu - game.opponent_mu)).exp());
+                // A certain result has no variance, the update would be NaN
+                let expected = expected.clamp(MIN_EXPECTED, 1.0 - MIN_EXPECTED);
+
+                (g, expected, game.score)
+            })
+            .collect();
+
+        let v = 1.0
+            / outcomes
+                .iter()
+                .map(|(g, expected, _)| g * g * expected * (1.0 - expected))
+                .sum::<f64>();
+        let improvement: f64 = outcomes
+            .iter()
+            .map(|(g, expected, score)| g * (score - expected))
+            .sum();
+        let delta = v * improvement;
+
+        let sigma = Glicko2::new_volatility(self.phi, self.sigma, v, delta, tau);
+        let phi_star = (self.phi * self.phi + sigma * sigma).sqrt();
+        let phi = 1.0 / (1.0 / (phi_star * phi_star) + 1.0 / v).sqrt();
+
+        Glicko2 {
+            mu: self.mu + phi * phi * improvement,
+            phi,
+            sigma,
+        }
+    }
+
+    /// Illinois iteration from step 5 of the Glicko-2 paper.
+    fn new_volatility(phi: f64, sigma: f64, v: f64, delta: f64, tau: f64) -> f64 {
+        let epsilon = 0.000_001;
+        let a = (sigma * sigma).ln();
+        let f = |x: f64| {
+            let ex = x.exp();
+            ex * (delta * delta - phi * phi - v - ex) / (2.0 * (phi * phi + v + ex).powi(2))
+                - (x - a) / (tau * tau)
+        };
+
+        let mut upper = a;
+        let mut lower = if delta * delta > phi * phi + v {
+            (delta * delta - phi * phi - v).ln()
+        } else {
+            let mut k = 1.0;
+            while f(a - k * tau) < 0.0 {
+                k += 1.0;
+            }
+            a - k * tau
+        };
+        let mut f_upper = f(upper);
+        let mut f_lower = f(lower);
+
+        while (lower - upper).abs() > epsilon {
+            let c = upper + (upper - lower) * f_upper / (f_lower - f_upper);
+            let f_c = f(c);
+
+            if f_c * f_lower < 0.0 {
+                upper = lower;
+                f_upper = f_lower;
+            } else {
+                f_upper /= 2.0;
+            }
+
+            lower = c;
+            f_lower = f_c;
+        }
+
+        (upper / 2.0).exp()
+    }
+}
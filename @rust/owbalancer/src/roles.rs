@@ -1,34 +1,88 @@
 use crate::matchmaking::Config;
 use crate::players::Classes;
 use crate::teams::Team;
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Role {
+    pub role: SimpleRole,
+    pub rank: i32,
+    pub sub_role: SubRole,
+    /// Rating points team strength takes off `rank`, see `LobbyOptions::conservative_factor`.
+    #[serde(default)]
+    pub uncertainty: i32,
+}
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub enum Role {
-    Tank((i32, bool, bool)),
-    Dps((i32, bool, bool)),
-    Support((i32, bool, bool)),
+/// Specialization within a role. `Any` is a player without a specialization,
+/// `Primary` and `Secondary` are used by roles without named sub roles.
+#[derive(
+    Serialize, Deserialize, JsonSchema, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[serde(rename_all = "camelCase")]
+pub enum SubRole {
+    Any,
+    MainTank,
+    OffTank,
+    Hitscan,
+    Projectile,
+    MainHeal,
+    LightHeal,
+    Primary,
+    Secondary,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-#[serde(rename_all = "lowercase")]
-pub enum SimpleRole {
-    Tank,
-    Dps,
-    Support,
+/// Role identifier as used in the lobby, e.g. `tank` or `duelist`.
+#[derive(
+    Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[serde(transparent)]
+pub struct SimpleRole(pub String);
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RoleSlots {
+    pub role: SimpleRole,
+    pub slots: usize,
+    /// Relative impact of the role on team strength, negative weights are rejected.
+    #[serde(
+        default = "RoleSlots::default_weight",
+        deserialize_with = "RoleSlots::deserialize_weight"
+    )]
+    pub weight: f32,
+    /// Roles a captain of this role takes a squire from, an empty list skips the squire
+    /// by role. Missing takes every role with a free slot next to the captain.
+    #[serde(default)]
+    pub squire_roles: Option<Vec<SimpleRole>>,
 }
 
+/// Roles a team consists of and how many players each role takes.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Composition(pub Vec<RoleSlots>);
+
 pub struct RolePriority {
     pub role: Role,
     pub priority: i16,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Default)]
 pub struct Roles(pub Vec<Role>);
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct RolesFilter(pub Vec<SimpleRole>);
 
+static NO_ROLE: Role = Role {
+    role: SimpleRole(String::new()),
+    rank: 0,
+    sub_role: SubRole::Any,
+    uncertainty: 0,
+};
+
+/// Rank lowered by its uncertainty, but not below 0.
+pub fn conservative_rank(rank: i32, uncertainty: i32) -> i32 {
+    rank - uncertainty.clamp(0, rank.max(0))
+}
+
 impl RolePriority {
     pub fn new(role: Role, priority: i16) -> RolePriority {
         RolePriority { role, priority }
@@ -36,135 +90,61 @@ impl RolePriority {
 }
 
 impl Role {
-    pub fn is_same(&self, role: &SimpleRole) -> bool {
-        match self {
-            Role::Dps(_) => *role == SimpleRole::Dps,
-            Role::Tank(_) => *role == SimpleRole::Tank,
-            Role::Support(_) => *role == SimpleRole::Support,
+    pub fn new(role: SimpleRole, rank: i32, sub_role: SubRole) -> Role {
+        Role {
+            role,
+            rank,
+            sub_role,
+            uncertainty: 0,
         }
     }
 
-    pub fn decompose(&self) -> (SimpleRole, i32) {
-        match self {
-            Role::Dps(rank) => (SimpleRole::Dps, rank.0),
-            Role::Support(rank) => (SimpleRole::Support, rank.0),
-            Role::Tank(rank) => (SimpleRole::Tank, rank.0),
-        }
+    pub fn is_same(&self, role: &SimpleRole) -> bool {
+        self.role == *role
     }
 
-    pub fn is_primary(&self) -> bool {
-        match self {
-            Role::Dps((_, primary, _)) => *primary,
-            Role::Support((_, primary, _)) => *primary,
-            Role::Tank((_, primary, _)) => *primary,
-        }
+    pub fn decompose(&self) -> (SimpleRole, i32) {
+        (self.role.clone(), self.rank)
     }
 
-    pub fn is_secondary(&self) -> bool {
-        match self {
-            Role::Dps((_, _, secondary)) => *secondary,
-            Role::Support((_, _, secondary)) => *secondary,
-            Role::Tank((_, _, secondary)) => *secondary,
-        }
+    pub fn sub_role(&self) -> SubRole {
+        self.sub_role
     }
 
     pub fn fits_team(&self, team: &Team, config: &Config) -> bool {
-        match self {
-            Role::Dps(rank) => {
-                if (team.dps_count() + 1) > 2 {
-                    return false;
-                }
-
-                if config.rank_limiter
-                    && rank.0 < config.limiter_max
-                    && team.low_dps_count(config.limiter_max) > 0
-                {
-                    return false;
-                }
-
-                if config.duplicate_roles && team.has_dps_duplicate(rank.1, rank.2) {
-                    return false;
-                }
-
-                true
-            }
-            Role::Support(rank) => {
-                if (team.support_count() + 1) > 2 {
-                    return false;
-                }
-
-                if config.rank_limiter
-                    && rank.0 < config.limiter_max
-                    && team.low_support_count(config.limiter_max) > 0
-                {
-                    return false;
-                }
-
-                if config.duplicate_roles && team.has_support_duplicate(rank.1, rank.2) {
-                    return false;
-                }
-
-                true
-            }
-            Role::Tank(rank) => {
-                if (team.tank_count() + 1) > 1 {
-                    return false;
-                }
-
-                if config.rank_limiter
-                    && rank.0 < config.limiter_max
-                    && team.low_tank_count(config.limiter_max) > 0
-                {
-                    return false;
-                }
-
-                if config.duplicate_roles && team.has_tank_duplicate(rank.1, rank.2) {
-                    return false;
-                }
-
-                true
-            }
+        if (team.count_role(&self.role) + 1) > config.composition.slots(&self.role) {
+            return false;
+        }
+
+        if !self.fits_team_limit(team, config) {
+            return false;
+        }
+
+        if config.duplicate_roles && team.has_sub_role_duplicate(&self.role, self.sub_role) {
+            return false;
         }
+
+        true
     }
 
     pub fn fits_team_limit(&self, team: &Team, config: &Config) -> bool {
-        match self {
-            Role::Dps(rank) => {
-                if config.rank_limiter
-                    && rank.0 < config.limiter_max
-                    && team.low_dps_count(config.limiter_max) > 0
-                {
-                    return false;
-                }
-
-                true
-            }
-            Role::Support(rank) => {
-                if config.rank_limiter
-                    && rank.0 < config.limiter_max
-                    && team.low_support_count(config.limiter_max) > 0
-                {
-                    return false;
-                }
-
-                true
-            }
-            Role::Tank(rank) => {
-                if config.rank_limiter
-                    && rank.0 < config.limiter_max
-                    && team.low_tank_count(config.limiter_max) > 0
-                {
-                    return false;
-                }
-
-                true
-            }
-        }
+        !(config.rank_limiter && self.is_limited(team, config))
+    }
+
+    /// Low ranked role for a team that already has a low ranked player on it.
+    pub fn is_limited(&self, team: &Team, config: &Config) -> bool {
+        self.rank < config.limiter_max && team.low_role_count(&self.role, config.limiter_max) > 0
+    }
+
+    pub fn is_in_range(&self, range: (i32, i32), config: &Config) -> bool {
+        let strength = self.strength(config);
+        strength >= range.0 && strength <= range.1
     }
 
-    pub fn is_in_range(&self, range: (i32, i32)) -> bool {
-        let rank = self.decompose().1;
-        rank >= range.0 && rank <= range.1
+    /// Rank weighted by the role impact, used for every team strength calculation.
+    pub fn strength(&self, config: &Config) -> i32 {
+        let rank = conservative_rank(self.rank, self.uncertainty);
+        config.composition.weighted_rank(&self.role, rank)
     }
 }
 
@@ -173,7 +153,7 @@ impl Roles {
         if let Some(role) = self.0.first() {
             role
         } else {
-            &Role::Dps((0, false, false))
+            &NO_ROLE
         }
     }
 
@@ -190,46 +170,40 @@ impl Roles {
     }
 
     pub fn get_by_simple(&self, simple: &SimpleRole) -> Option<&Role> {
+        self.0.iter().find(|&role| role.is_same(simple))
+    }
+
+    /// 1-based position of the role in the player's preference order.
+    pub fn priority_of(&self, simple: &SimpleRole) -> Option<usize> {
         self.0
             .iter()
-            .find(|&role| role.decompose().0 == simple.clone())
+            .position(|role| role.is_same(simple))
+            .map(|index| index + 1)
     }
 }
 
 impl From<&Classes> for Roles {
     fn from(classes: &Classes) -> Self {
-        let mut temp_roles: Vec<RolePriority> = Vec::new();
-
-        if classes.dps.is_active {
-            temp_roles.push(RolePriority::new(
-                Role::Dps((classes.dps.rank, classes.dps.primary, classes.dps.secondary)),
-                classes.dps.priority,
-            ));
-        }
-
-        if classes.support.is_active {
-            temp_roles.push(RolePriority::new(
-                Role::Support((
-                    classes.support.rank,
-                    classes.support.primary,
-                    classes.support.secondary,
-                )),
-                classes.support.priority,
-            ));
-        }
-
-        if classes.tank.is_active {
-            temp_roles.push(RolePriority::new(
-                Role::Tank((
-                    classes.tank.rank,
-                    classes.tank.primary,
-                    classes.tank.secondary,
-                )),
-                classes.tank.priority,
-            ));
-        }
-
-        temp_roles.sort_by(|a, b| a.priority.cmp(&b.priority));
+        let mut temp_roles: Vec<RolePriority> = classes
+            .0
+            .iter()
+            .filter(|(_, class)| class.is_active)
+            .map(|(role, class)| {
+                RolePriority::new(
+                    Role {
+                        uncertainty: class.uncertainty,
+                        ..Role::new(role.clone(), class.rank, class.sub_role(role))
+                    },
+                    class.priority,
+                )
+            })
+            .collect();
+
+        temp_roles.sort_by(|a, b| {
+            a.priority
+                .cmp(&b.priority)
+                .then_with(|| a.role.role.cmp(&b.role.role))
+        });
 
         let mut roles = Vec::default();
 
@@ -242,11 +216,167 @@ impl From<&Classes> for Roles {
 }
 
 impl SimpleRole {
+    pub fn new(role: &str) -> SimpleRole {
+        SimpleRole(role.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
     pub fn get_string(&self) -> String {
-        match self {
-            SimpleRole::Dps => "dps".to_string(),
-            SimpleRole::Tank => "tank".to_string(),
-            SimpleRole::Support => "support".to_string(),
+        self.0.clone()
+    }
+}
+
+impl Composition {
+    pub fn slots(&self, role: &SimpleRole) -> usize {
+        self.0
+            .iter()
+            .find(|slots| slots.role == *role)
+            .map_or(0, |slots| slots.slots)
+    }
+
+    pub fn team_size(&self) -> usize {
+        self.0.iter().map(|slots| slots.slots).sum()
+    }
+
+    pub fn roles(&self) -> impl Iterator<Item = &SimpleRole> {
+        self.0.iter().map(|slots| &slots.role)
+    }
+
+    /// Roles a captain of `role` takes a squire from.
+    pub fn squire_roles(&self, role: &SimpleRole) -> Vec<SimpleRole> {
+        let configured = self
+            .0
+            .iter()
+            .find(|slots| slots.role == *role)
+            .and_then(|slots| slots.squire_roles.clone());
+
+        configured.unwrap_or_else(|| {
+            self.roles()
+                .filter(|&squire_role| squire_role != role || self.slots(squire_role) > 1)
+                .cloned()
+                .collect()
+        })
+    }
+
+    /// Role weight normalized so that a full team has an average weight of 1.
+    pub fn weight(&self, role: &SimpleRole) -> f32 {
+        let total: f32 = self
+            .0
+            .iter()
+            .map(|slots| slots.weight * slots.slots as f32)
+            .sum();
+        let role_weight = self
+            .0
+            .iter()
+            .find(|slots| slots.role == *role)
+            .map_or(1.0, |slots| slots.weight);
+
+        if total <= 0.0 {
+            return 1.0;
+        }
+
+        role_weight * self.team_size() as f32 / total
+    }
+
+    pub fn weighted_rank(&self, role: &SimpleRole, rank: i32) -> i32 {
+        (rank as f32 * self.weight(role)).round() as i32
+    }
+}
+
+impl RoleSlots {
+    fn default_weight() -> f32 {
+        1.0
+    }
+
+    fn deserialize_weight<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f32, D::Error> {
+        let weight = f32::deserialize(deserializer)?;
+
+        if weight.is_finite() && weight >= 0.0 {
+            Ok(weight)
+        } else {
+            Err(serde::de::Error::custom(format!(
+                "role weight {} is not a finite non-negative number",
+                weight
+            )))
+        }
+    }
+}
+
+/// Overwatch 5v5: one tank, two damage and two support players. Tank captains take a
+/// damage or support squire, damage captains a tank or damage one and support captains
+/// get theirs with the leftover squires.
+impl Default for Composition {
+    fn default() -> Self {
+        let roles = |roles: &[&str]| Some(roles.iter().map(|role| SimpleRole::new(role)).collect());
+
+        Composition(vec![
+            RoleSlots {
+                role: SimpleRole::new("tank"),
+                slots: 1,
+                weight: 1.0,
+                squire_roles: roles(&["dps", "support"]),
+            },
+            RoleSlots {
+                role: SimpleRole::new("dps"),
+                slots: 2,
+                weight: 1.0,
+                squire_roles: roles(&["tank", "dps"]),
+            },
+            RoleSlots {
+                role: SimpleRole::new("support"),
+                slots: 2,
+                weight: 1.0,
+                squire_roles: roles(&[]),
+            },
+        ])
+    }
+}
+
+impl SubRole {
+    pub fn new(role: &SimpleRole, primary: bool, secondary: bool) -> SubRole {
+        match (role.as_str(), primary, secondary) {
+            ("tank", true, _) => SubRole::MainTank,
+            ("tank", false, true) => SubRole::OffTank,
+            ("dps", true, _) => SubRole::Hitscan,
+            ("dps", false, true) => SubRole::Projectile,
+            ("support", true, _) => SubRole::MainHeal,
+            ("support", false, true) => SubRole::LightHeal,
+            (_, true, _) => SubRole::Primary,
+            (_, false, true) => SubRole::Secondary,
+            _ => SubRole::Any,
+        }
+    }
+
+    pub fn is_primary(&self) -> bool {
+        matches!(
+            self,
+            SubRole::MainTank | SubRole::Hitscan | SubRole::MainHeal | SubRole::Primary
+        )
+    }
+
+    pub fn is_secondary(&self) -> bool {
+        matches!(
+            self,
+            SubRole::OffTank | SubRole::Projectile | SubRole::LightHeal | SubRole::Secondary
+        )
+    }
+
+    /// Sub roles other than `Any` may appear only once per team when duplicates are disallowed.
+    pub fn is_specialized(&self) -> bool {
+        *self != SubRole::Any
+    }
+
+    /// Rating adjustment curve used for the sub role.
+    pub fn specialization(&self) -> &'static str {
+        if self.is_primary() {
+            "primary"
+        } else if self.is_secondary() {
+            "secondary"
+        } else {
+            "any"
         }
     }
 }
@@ -1,13 +1,15 @@
+use crate::arena::{no_player, Reserve};
 use crate::matchmaking::Config;
 use crate::players::{Candidate, Direction, PlayerPool, Players};
-use crate::roles::{Role, RolesFilter, SimpleRole};
+use crate::roles::{conservative_rank, Role, RolesFilter, SimpleRole, SubRole};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::VecDeque;
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 pub struct Member {
     pub rank: i32,
     pub uuid: String,
@@ -15,9 +17,15 @@ pub struct Member {
     pub primary: bool,
     pub secondary: bool,
     pub role: SimpleRole,
+    /// Rating points team strength takes off `rank`, see `LobbyOptions::conservative_factor`.
+    #[serde(default)]
+    pub uncertainty: i32,
+    /// Player index in the `Arena`, `NO_PLAYER` until the arena sets it.
+    #[serde(skip, default = "no_player")]
+    pub index: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Team {
     pub uuid: Uuid,
@@ -25,31 +33,57 @@ pub struct Team {
     pub name: String,
     pub total_sr: i32,
     pub members: Vec<Member>,
+    /// Plain mean of member ranks, `avg_sr` is weighted by role.
+    #[serde(default)]
+    pub raw_avg_sr: f32,
+    /// Uncertainty of `raw_avg_sr` from the member rank deviations.
+    #[serde(default)]
+    pub deviation: f64,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Default, Clone)]
 pub struct Teams(pub Vec<Team>);
 
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SubRoleDuplicate {
+    pub team: String,
+    pub role: SimpleRole,
+    pub sub_role: SubRole,
+}
+
 impl Member {
-    pub fn new(uuid: String, name: String, role: Role) -> Member {
+    pub fn new(uuid: String, name: String, index: usize, role: Role) -> Member {
         let (simple, rank) = role.decompose();
+        let sub_role = role.sub_role();
 
         Member {
             uuid,
             name,
             rank,
+            uncertainty: role.uncertainty,
             role: simple,
-            primary: role.is_primary(),
-            secondary: role.is_secondary(),
+            primary: sub_role.is_primary(),
+            secondary: sub_role.is_secondary(),
+            index,
         }
     }
 
+    pub fn sub_role(&self) -> SubRole {
+        SubRole::new(&self.role, self.primary, self.secondary)
+    }
+
     pub fn from_primary_player(candidate: &Candidate) -> Member {
         let uuid = candidate.uuid.clone();
         let name = candidate.name.clone();
         let role = candidate.roles.get_primary().clone();
 
-        return Member::new(uuid, name, role);
+        return Member::new(uuid, name, candidate.index, role);
+    }
+
+    pub fn strength(&self, config: &Config) -> i32 {
+        let rank = conservative_rank(self.rank, self.uncertainty);
+        config.composition.weighted_rank(&self.role, rank)
     }
 
     pub fn has_same_role(&self, player: &Candidate) -> bool {
@@ -57,18 +91,6 @@ impl Member {
 
         primary_role.is_same(&self.role)
     }
-
-    fn is_dps(&self) -> bool {
-        self.role == SimpleRole::Dps
-    }
-
-    fn is_tank(&self) -> bool {
-        self.role == SimpleRole::Tank
-    }
-
-    fn is_support(&self) -> bool {
-        self.role == SimpleRole::Support
-    }
 }
 
 impl Team {
@@ -83,6 +105,8 @@ impl Team {
             members,
             avg_sr: 0.0,
             total_sr: 0,
+            raw_avg_sr: 0.0,
+            deviation: 0.0,
         }
     }
 
@@ -94,16 +118,13 @@ impl Team {
         self.members.get(1).unwrap()
     }
 
-    pub fn get_leutenant(&self) -> &Member {
-        self.members.get(2).unwrap()
+    /// `None` for teams that did not get a lieutenant, e.g. lobbies without squires.
+    pub fn get_leutenant(&self) -> Option<&Member> {
+        self.members.get(2)
     }
 
-    pub fn fits_role(&self, role: &Role) -> bool {
-        match role {
-            Role::Tank(_) => self.tank_count() < 1,
-            Role::Dps(_) => self.dps_count() < 2,
-            Role::Support(_) => self.support_count() < 2,
-        }
+    pub fn fits_role(&self, role: &Role, config: &Config) -> bool {
+        self.count_role(&role.role) < config.composition.slots(&role.role)
     }
 
     pub fn add_primary_player(&mut self, candidate: &Candidate) {
@@ -114,106 +135,103 @@ impl Team {
         self.members.push(Member::new(
             candidate.uuid.clone(),
             candidate.name.clone(),
+            candidate.index,
             target_role.clone(),
         ));
     }
 
-    pub fn dps_count(&self) -> usize {
-        self.count_role(&SimpleRole::Dps)
-    }
-
     pub fn low_role_count(&self, role: &SimpleRole, threshold: i32) -> usize {
-        match role {
-            SimpleRole::Dps => self.low_dps_count(threshold),
-            SimpleRole::Tank => self.low_tank_count(threshold),
-            SimpleRole::Support => self.low_support_count(threshold),
-        }
+        self.count_role_lows(role, threshold)
     }
 
+    /// Number of roles where more than one player is below the threshold.
     pub fn total_low_role_count(&self, threshold: i32) -> usize {
-        let dps = if self.low_dps_count(threshold) > 1 {
-            1
-        } else {
-            0
-        };
-        let support = if self.low_support_count(threshold) > 1 {
-            1
-        } else {
-            0
-        };
-        let tank = if self.low_tank_count(threshold) > 1 {
-            1
-        } else {
-            0
-        };
+        let mut roles: Vec<&SimpleRole> = self.members.iter().map(|member| &member.role).collect();
+        roles.sort();
+        roles.dedup();
 
-        dps + support + tank
+        roles
+            .into_iter()
+            .filter(|role| self.low_role_count(role, threshold) > 1)
+            .count()
     }
 
-    pub fn low_dps_count(&self, threshold: i32) -> usize {
-        self.count_role_lows(&SimpleRole::Dps, threshold)
+    pub fn has_sub_role_duplicate(&self, role: &SimpleRole, sub_role: SubRole) -> bool {
+        self.find_duplicates(role, sub_role)
     }
 
-    pub fn has_dps_duplicate(&self, primary: bool, secondary: bool) -> bool {
-        self.find_duplicates(&SimpleRole::Dps, primary, secondary)
-    }
+    /// Whether `member` in place of the member at `position` takes a specialized sub role twice.
+    pub fn duplicates_sub_role(&self, position: usize, member: &Member) -> bool {
+        let sub_role = member.sub_role();
 
-    pub fn support_count(&self) -> usize {
-        self.count_role(&SimpleRole::Support)
+        sub_role.is_specialized()
+            && self.members.iter().enumerate().any(|(index, mate)| {
+                index != position && mate.role == member.role && mate.sub_role() == sub_role
+            })
     }
 
-    pub fn has_support_duplicate(&self, primary: bool, secondary: bool) -> bool {
-        self.find_duplicates(&SimpleRole::Support, primary, secondary)
-    }
+    /// Specialized sub roles taken by more than one member.
+    pub fn duplicate_sub_roles(&self) -> Vec<(SimpleRole, SubRole)> {
+        let mut seen = Vec::default();
+        let mut duplicates = Vec::default();
 
-    pub fn low_support_count(&self, threshold: i32) -> usize {
-        self.count_role_lows(&SimpleRole::Support, threshold)
-    }
+        for member in &self.members {
+            let sub_role = (member.role.clone(), member.sub_role());
 
-    pub fn tank_count(&self) -> usize {
-        self.count_role(&SimpleRole::Tank)
-    }
+            if !sub_role.1.is_specialized() {
+                continue;
+            }
 
-    pub fn has_tank_duplicate(&self, primary: bool, secondary: bool) -> bool {
-        self.find_duplicates(&SimpleRole::Tank, primary, secondary)
-    }
+            if !seen.contains(&sub_role) {
+                seen.push(sub_role);
+            } else if !duplicates.contains(&sub_role) {
+                duplicates.push(sub_role);
+            }
+        }
 
-    pub fn low_tank_count(&self, threshold: i32) -> usize {
-        self.count_role_lows(&SimpleRole::Tank, threshold)
+        duplicates
     }
 
-    pub fn update(&mut self) -> (f32, i32) {
-        let total_sr = self.total_sr();
+    pub fn update(&mut self, config: &Config) -> (f32, i32) {
+        let total_sr = self.total_sr(config);
         let avg_sr = total_sr as f32 / self.members_count() as f32;
+        let raw_sr: i32 = self.members.iter().map(|member| member.rank).sum();
 
         self.avg_sr = avg_sr;
         self.total_sr = total_sr;
+        self.raw_avg_sr = raw_sr as f32 / self.members_count() as f32;
 
         (avg_sr, total_sr)
     }
 
+    pub fn update_deviation(&mut self, players: &Players) {
+        let variance: f64 = self
+            .members
+            .iter()
+            .map(|member| players.get_deviation(&member.uuid, &member.role).powi(2))
+            .sum();
+
+        self.deviation = variance.sqrt() / self.members_count().max(1) as f64;
+    }
+
     pub fn members_count(&self) -> usize {
         self.members.len()
     }
 
-    pub fn get_missing_role(&self) -> Option<SimpleRole> {
-        if self.support_count() < 2 {
-            return Some(SimpleRole::Support);
-        }
-
-        if self.dps_count() < 2 {
-            return Some(SimpleRole::Dps);
-        }
-
-        if self.tank_count() < 1 {
-            return Some(SimpleRole::Tank);
-        }
+    pub fn get_missing_role(&self, config: &Config) -> Option<SimpleRole> {
+        config
+            .composition
+            .roles()
+            .find(|&role| self.count_role(role) < config.composition.slots(role))
+            .cloned()
+    }
 
-        None
+    pub fn is_complete(&self, config: &Config) -> bool {
+        self.members_count() >= config.composition.team_size()
     }
 
     pub fn get_range(&self, config: &Config) -> (i32, i32) {
-        let players_count = 5;
+        let players_count = config.composition.team_size() as u32;
         let tolerance_range = config.tolerance * players_count;
 
         let target_sr = if self.members_count() as u32 != players_count {
@@ -234,7 +252,7 @@ impl Team {
         candidate: &Candidate,
         target_role: &SimpleRole,
         range: (i32, i32),
-        db: &PlayerPool,
+        reserve: &Reserve,
         target_team: &Team,
         config: &Config,
     ) -> Option<usize> {
@@ -243,7 +261,7 @@ impl Team {
             let position = self.members.iter().position(|member| {
                 let is_self = target_team.uuid == self.uuid;
 
-                if member.role != simple.clone() {
+                if member.role != simple.clone() || config.arena.is_anchor(member.index) {
                     return false;
                 }
 
@@ -251,13 +269,13 @@ impl Team {
                     return false;
                 }
 
-                if let Some(player) = db.get_by_id(member.uuid.clone()) {
+                if let Some(player) = reserve.get(&config.arena, member.index) {
                     if let Some(player_role) = player.roles.get_by_simple(&target_role) {
                         if is_self && !self.pfsr2(candidate, member, config, player_role) {
                             return false;
                         }
 
-                        return player_role.is_in_range(range)
+                        return player_role.is_in_range(range, config)
                             && player_role.fits_team_limit(target_team, config);
                     }
                 }
@@ -275,10 +293,11 @@ impl Team {
 
     pub fn pfsr(&self, candidate: &Candidate, member: &Member, config: &Config) -> bool {
         if let Some(role) = candidate.roles.get_by_simple(&member.role) {
-            let rank = role.decompose().1;
+            let rank = role.strength(config);
+            let member_rank = member.strength(config);
             let new_average = (config.total_sr + rank) as f32 / (config.total_count + 1) as f32;
             let team_size = self.members_count();
-            let new_sr = (self.total_sr + rank - member.rank) as f32 / team_size as f32;
+            let new_sr = (self.total_sr + rank - member_rank) as f32 / team_size as f32;
 
             ((new_sr - new_average).abs().floor() as u32) <= config.tolerance
                 && role.fits_team_limit(&self, config)
@@ -295,12 +314,13 @@ impl Team {
         player_role: &Role,
     ) -> bool {
         if let Some(role) = candidate.roles.get_by_simple(&member.role) {
-            let rank = role.decompose().1;
-            let rank2 = player_role.decompose().1;
-            let new_average = (config.total_sr - member.rank + rank2 + rank) as f32
+            let rank = role.strength(config);
+            let rank2 = player_role.strength(config);
+            let member_rank = member.strength(config);
+            let new_average = (config.total_sr - member_rank + rank2 + rank) as f32
                 / (config.total_count + 1) as f32;
             let team_size = self.members_count();
-            let new_sr = (self.total_sr + rank + rank2 - member.rank) as f32 / team_size as f32;
+            let new_sr = (self.total_sr + rank + rank2 - member_rank) as f32 / team_size as f32;
 
             ((new_sr - new_average).abs().floor() as u32) <= config.tolerance
                 && role.fits_team_limit(&self, config)
@@ -309,31 +329,23 @@ impl Team {
         }
     }
 
-    pub fn fits_sr(&self, player_sr: i32, new_average: f32, tolerance: u32) -> bool {
+    pub fn fits_sr(&self, player_sr: i32, new_average: f32, config: &Config) -> bool {
         let team_size = self.members_count();
+        let free_slots = config.composition.team_size().saturating_sub(team_size) as u32;
         let new_sr = (self.total_sr + player_sr) as f32 / (team_size + 1) as f32;
-        ((new_sr - new_average).abs().floor() as u32) <= tolerance * (5 - team_size as u32)
+        ((new_sr - new_average).abs().floor() as u32) <= config.tolerance * free_slots
     }
 
-    pub fn can_swap(
-        &self,
-        team: &Team,
-        config: &Config,
-        players: &Players,
-    ) -> Option<(usize, usize)> {
+    pub fn can_swap(&self, team: &Team, config: &Config) -> Option<(usize, usize)> {
         let avg = config.total_sr / config.total_count as i32;
 
         for (i, mem) in self.members.iter().enumerate() {
-            let p1_base = &players.0.get(mem.uuid.as_str()).unwrap();
-            let p1 = &p1_base.identity;
-            if p1.is_captain || p1.is_squire {
+            if config.arena.is_anchor(mem.index) {
                 continue;
             }
 
             for (j, mem2) in team.members.iter().enumerate() {
-                let p2_base = &players.0.get(mem2.uuid.as_str()).unwrap();
-                let p2 = &p2_base.identity;
-                if p2.is_captain || p2.is_squire {
+                if config.arena.is_anchor(mem2.index) {
                     continue;
                 }
 
@@ -349,54 +361,47 @@ impl Team {
                     }
 
                     if config.duplicate_roles2 {
-                        let partner1 = self.get_partner(&mem.role, &mem.uuid);
-                        let partner2 = team.get_partner(&mem2.role, &mem2.uuid);
+                        let partner1 = self.get_partner(&mem.role, mem.index);
+                        let partner2 = team.get_partner(&mem2.role, mem2.index);
 
                         if partner1.is_none() || partner2.is_none() {
                             continue;
                         }
 
-                        let partner1 = partner1.unwrap();
-                        let partner2 = partner2.unwrap();
-
-                        let partner1_base = &players.0.get(partner1.uuid.as_str()).unwrap();
-                        let partner2_base = &players.0.get(partner2.uuid.as_str()).unwrap();
+                        let partner1 = partner1.unwrap().sub_role();
+                        let partner2 = partner2.unwrap().sub_role();
 
-                        let mem1_class = p1_base.stats.classes.get_class(&mem.role);
-                        let mem2_class = p2_base.stats.classes.get_class(&mem2.role);
+                        let mem1_sub_role = mem.sub_role();
+                        let mem2_sub_role = mem2.sub_role();
 
-                        let partner1_class = partner1_base.stats.classes.get_class(&mem.role);
-                        let partner2_class = partner2_base.stats.classes.get_class(&mem.role);
-
-                        if (partner1_class.primary && partner1_class.primary == mem2_class.primary)
-                            || (partner1_class.secondary
-                                && partner1_class.secondary == mem2_class.secondary)
-                            || (partner2_class.primary
-                                && partner2_class.primary == mem1_class.primary)
-                            || (partner2_class.secondary
-                                && partner2_class.secondary == mem1_class.secondary)
+                        if (partner1.is_specialized() && partner1 == mem2_sub_role)
+                            || (partner2.is_specialized() && partner2 == mem1_sub_role)
                         {
                             continue;
                         }
                     }
 
-                    let global_role_avg = *config.roles_avg.get(&mem.role.get_string()).unwrap();
+                    let global_role_avg = *config.roles_avg.get(&mem.role).unwrap();
+                    let role_slots = config.composition.slots(&mem.role).max(1) as i32;
 
+                    let strength1 = mem.strength(config);
+                    let strength2 = mem2.strength(config);
                     let newsr =
-                        (self.total_sr - mem.rank + mem2.rank) / self.members_count() as i32;
+                        (self.total_sr - strength1 + strength2) / self.members_count() as i32;
                     let newsr2 =
-                        (team.total_sr - mem2.rank + mem.rank) / team.members_count() as i32;
+                        (team.total_sr - strength2 + strength1) / team.members_count() as i32;
                     let newdisp1 = (newsr - avg).abs();
                     let newdisp2 = (newsr2 - avg).abs();
 
                     let team1_role_sr = self.total_role_sr(&mem.role);
                     let team2_role_sr = team.total_role_sr(&mem2.role);
 
-                    let team1_role_avg = team1_role_sr / 2;
-                    let team2_role_avg = team2_role_sr / 2;
+                    let team1_role_avg = team1_role_sr / role_slots;
+                    let team2_role_avg = team2_role_sr / role_slots;
 
-                    let new_role_avg = (team1_role_sr - mem.rank + mem2.rank) / 2;
-                    let new_role_avg2 = (team.total_role_sr(&mem2.role) - mem2.rank + mem.rank) / 2;
+                    let new_role_avg = (team1_role_sr - mem.rank + mem2.rank) / role_slots;
+                    let new_role_avg2 =
+                        (team.total_role_sr(&mem2.role) - mem2.rank + mem.rank) / role_slots;
 
                     let role_disp1 = (team1_role_avg - global_role_avg).abs();
                     let role_disp2 = (team2_role_avg - global_role_avg).abs();
@@ -404,6 +409,18 @@ impl Team {
                     let new_role_disp1 = (new_role_avg - global_role_avg).abs();
                     let new_role_disp2 = (new_role_avg2 - global_role_avg).abs();
 
+                    // Do not trade balance for teammates that already played together
+                    let repeats = config.arena.repeats_with(self, mem.index)
+                        + config.arena.repeats_with(team, mem2.index);
+                    let mate_repeats = config.arena.count(mem.index, mem2.index);
+                    let new_repeats = config.arena.repeats_with(team, mem.index)
+                        + config.arena.repeats_with(self, mem2.index)
+                        - 2 * mate_repeats;
+
+                    if new_repeats > repeats {
+                        continue;
+                    }
+
                     if new_role_disp1 < role_disp1
                         && new_role_disp2 < role_disp2
                         && newdisp1 <= config.tolerance as i32
@@ -418,14 +435,29 @@ impl Team {
         None
     }
 
-    fn get_partner(&self, role: &SimpleRole, uuid: &String) -> Option<&Member> {
+    /// Members as `(uuid, role)` pairs in a stable order, independent of insertion order.
+    pub fn lineup(&self) -> Vec<(String, SimpleRole)> {
+        let mut lineup: Vec<(String, SimpleRole)> = self
+            .members
+            .iter()
+            .map(|member| (member.uuid.clone(), member.role.clone()))
+            .collect();
+
+        lineup.sort();
+        lineup
+    }
+
+    fn get_partner(&self, role: &SimpleRole, index: usize) -> Option<&Member> {
         self.members
             .iter()
-            .find(|&member| member.uuid != uuid.clone() && member.role == *role)
+            .find(|&member| member.index != index && member.role == *role)
     }
 
-    fn total_sr(&self) -> i32 {
-        self.members.iter().map(|member| member.rank).sum()
+    fn total_sr(&self, config: &Config) -> i32 {
+        self.members
+            .iter()
+            .map(|member| member.strength(config))
+            .sum()
     }
 
     pub fn total_role_sr(&self, role: &SimpleRole) -> i32 {
@@ -436,7 +468,7 @@ impl Team {
             .sum()
     }
 
-    fn count_role(&self, role: &SimpleRole) -> usize {
+    pub fn count_role(&self, role: &SimpleRole) -> usize {
         self.members
             .iter()
             .filter(|&member| member.role == *role)
@@ -450,30 +482,12 @@ impl Team {
             .count()
     }
 
-    fn find_duplicates(&self, role: &SimpleRole, primary: bool, secondary: bool) -> bool {
-        if primary {
-            if self
+    fn find_duplicates(&self, role: &SimpleRole, sub_role: SubRole) -> bool {
+        sub_role.is_specialized()
+            && self
                 .members
                 .iter()
-                .find(|&member| member.role == *role && member.primary)
-                .is_some()
-            {
-                return true;
-            }
-        }
-
-        if secondary {
-            if self
-                .members
-                .iter()
-                .find(|&member| member.role == *role && member.secondary)
-                .is_some()
-            {
-                return true;
-            }
-        }
-
-        false
+                .any(|member| member.role == *role && member.sub_role() == sub_role)
     }
 }
 
@@ -482,9 +496,15 @@ impl Teams {
         self.0.reverse();
     }
 
-    pub fn update(&mut self) {
+    pub fn update(&mut self, config: &Config) {
         for team in &mut self.0 {
-            team.update();
+            team.update(config);
+        }
+    }
+
+    pub fn update_deviation(&mut self, players: &Players) {
+        for team in &mut self.0 {
+            team.update_deviation(players);
         }
     }
 
@@ -515,23 +535,28 @@ impl Teams {
         (total_sr, total_count)
     }
 
-    pub fn swap(&mut self, team1: usize, member1: usize, team2: usize, member2: usize) {
-        let memb1 = self.get(team1).members.get(member1).unwrap().clone();
-        let memb2 = self.get(team2).members.get(member2).unwrap().clone();
-        self.get_mut(team1).members.remove(member1);
-        self.get_mut(team2).members.remove(member2);
+    pub fn swap(
+        &mut self,
+        team1: usize,
+        member1: usize,
+        team2: usize,
+        member2: usize,
+        config: &Config,
+    ) {
+        let memb1 = self.get_mut(team1).members.remove(member1);
+        let memb2 = self.get_mut(team2).members.remove(member2);
         self.get_mut(team1).members.push(memb2);
         self.get_mut(team2).members.push(memb1);
-        self.get_mut(team1).update();
-        self.get_mut(team2).update();
+        self.get_mut(team1).update(config);
+        self.get_mut(team2).update(config);
     }
 
-    pub fn get_not_complete(&self) -> Vec<usize> {
+    pub fn get_not_complete(&self, config: &Config) -> Vec<usize> {
         self.0
             .iter()
             .enumerate()
             .filter_map(|(id, team)| {
-                if team.members_count() < 5 {
+                if !team.is_complete(config) {
                     Some(id)
                 } else {
                     None
@@ -549,13 +574,13 @@ impl Teams {
         leftover: &Candidate,
         role: &SimpleRole,
         range: (i32, i32),
-        db: &PlayerPool,
+        reserve: &Reserve,
         target_team: &Team,
         config: &Config,
     ) -> Option<(usize, usize)> {
         for (team_index, team) in self.0.iter().enumerate() {
             if let Some(replacement) =
-                team.try_replace(leftover, role, range, db, target_team, config)
+                team.try_replace(leftover, role, range, reserve, target_team, config)
             {
                 return Some((team_index, replacement));
             }
@@ -590,64 +615,61 @@ impl Teams {
         })
     }
 
-    pub fn filter_dps_captains(&mut self) -> Vec<&mut Team> {
-        self.0
-            .iter_mut()
-            .filter(|team| team.get_captain().is_dps())
-            .collect()
-    }
-
-    pub fn filter_tank_captains(&mut self) -> Vec<&mut Team> {
+    pub fn filter_captains(&mut self, role: &SimpleRole) -> Vec<&mut Team> {
         self.0
             .iter_mut()
-            .filter(|team| team.get_captain().is_tank())
+            .filter(|team| team.get_captain().role == *role)
             .collect()
     }
 
-    pub fn distribute_squires_to_tanks(&mut self, squires: &mut PlayerPool) {
-        let roles_filter = RolesFilter(vec![SimpleRole::Dps, SimpleRole::Support]);
-
-        let tank_teams = self.filter_tank_captains();
-        let mut worthy_squires = squires.filter_by_roles(roles_filter);
-        let mut cache_squires = Vec::default();
-
-        for team in tank_teams {
-            if worthy_squires.is_empty() {
-                break;
-            }
-
-            let candidate = worthy_squires.pop().unwrap();
-            cache_squires.push(candidate);
-            team.add_primary_player(squires.0.get(candidate).unwrap());
-        }
+    /// Gives captains of each role a squire of one of their `Composition::squire_roles`,
+    /// starting with the roles that have the fewest slots.
+    pub fn distribute_squires_by_role(&mut self, squires: &mut PlayerPool, config: &Config) {
+        let mut roles: Vec<&SimpleRole> = config.composition.roles().collect();
+        roles.sort_by_key(|role| config.composition.slots(role));
 
-        squires.dispose_of(cache_squires);
-    }
+        for role in roles {
+            let roles_filter = RolesFilter(config.composition.squire_roles(role));
 
-    pub fn distribute_squires_to_dps(&mut self, squires: &mut PlayerPool) {
-        let roles_filter = RolesFilter(vec![SimpleRole::Tank, SimpleRole::Dps]);
+            let teams = self.filter_captains(role);
+            let mut worthy_squires = squires.filter_by_roles(roles_filter);
+            let mut cache_squires = Vec::default();
 
-        let dps_teams = self.filter_dps_captains();
-        let mut worthy_squires = squires.filter_by_roles(roles_filter);
-        let mut cache_squires = Vec::default();
+            for team in teams {
+                if worthy_squires.is_empty() {
+                    break;
+                }
 
-        for team in dps_teams {
-            if worthy_squires.is_empty() {
-                break;
+                // Latest squire with the fewest games played alongside the captain
+                let position = worthy_squires
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .min_by_key(|(_, &index)| {
+                        config.arena.repeats_with(team, squires.0[index].index)
+                    })
+                    .map(|(position, _)| position)
+                    .unwrap();
+                let candidate = worthy_squires.remove(position);
+                cache_squires.push(candidate);
+                team.add_primary_player(squires.0.get(candidate).unwrap());
             }
 
-            let candidate = worthy_squires.pop().unwrap();
-            cache_squires.push(candidate);
-            team.add_primary_player(squires.0.get(candidate).unwrap());
+            squires.dispose_of(cache_squires);
         }
-
-        squires.dispose_of(cache_squires);
     }
 
-    pub fn distribute_squires(&mut self, squires: &mut PlayerPool) {
+    /// Squires left after `distribute_squires_by_role`, only on roles the team has room for.
+    pub fn distribute_squires(&mut self, squires: &mut PlayerPool, config: &Config) {
         for team in &mut self.0 {
             if team.members_count() < 2 {
-                if let Some(candidate) = squires.0.pop() {
+                let position = squires
+                    .0
+                    .iter()
+                    .rposition(|candidate| team.fits_role(candidate.get_primary_role(), config));
+
+                if let Some(position) = position {
+                    let candidate = squires.0.remove(position);
                     team.add_primary_player(&candidate);
                 }
             }
@@ -680,11 +702,14 @@ impl Teams {
         max_member_count: usize,
         config: &Config,
     ) -> Option<&mut Team> {
-        self.0.iter_mut().find(|team| {
-            team.members_count() <= max_member_count
-                && candidate.get_primary_role().fits_team(team, config)
-                && !team.get_captain().has_same_role(candidate)
-        })
+        self.0
+            .iter_mut()
+            .filter(|team| {
+                team.members_count() <= max_member_count
+                    && candidate.get_primary_role().fits_team(team, config)
+                    && !team.get_captain().has_same_role(candidate)
+            })
+            .min_by_key(|team| config.arena.repeats_with(team, candidate.index))
     }
 
     pub fn find_perfect_ensign(
@@ -692,23 +717,31 @@ impl Teams {
         candidate: &Candidate,
         config: &Config,
     ) -> Option<&mut Team> {
-        self.0.iter_mut().find(|team| {
-            team.members_count() <= 3
-                && candidate.get_primary_role().fits_team(team, config)
-                && !team.get_captain().has_same_role(candidate)
-                && !team.get_leutenant().has_same_role(candidate)
-        })
+        self.0
+            .iter_mut()
+            .filter(|team| {
+                team.members_count() <= 3
+                    && candidate.get_primary_role().fits_team(team, config)
+                    && !team.get_captain().has_same_role(candidate)
+                    && !team
+                        .get_leutenant()
+                        .is_some_and(|leutenant| leutenant.has_same_role(candidate))
+            })
+            .min_by_key(|team| config.arena.repeats_with(team, candidate.index))
     }
 
     pub fn find_team(
         &mut self,
         max_size: usize,
-        target_role: &Role,
+        candidate: &Candidate,
         config: &Config,
     ) -> Option<&mut Team> {
+        let target_role = candidate.get_primary_role();
+
         self.0
             .iter_mut()
-            .find(|team| team.members_count() <= max_size && target_role.fits_team(team, config))
+            .filter(|team| team.members_count() <= max_size && target_role.fits_team(team, config))
+            .min_by_key(|team| config.arena.repeats_with(team, candidate.index))
     }
 
     pub fn fit_player(
@@ -716,6 +749,7 @@ impl Teams {
         player_sr: i32,
         new_average: f32,
         config: &Config,
+        candidate: &Candidate,
         target_role: &Role,
     ) -> Option<&mut Team> {
         let mut teams: Vec<usize> = self
@@ -725,9 +759,9 @@ impl Teams {
             .filter_map(|(index, team)| {
                 let team_size = team.members_count();
 
-                if (team_size + 1) <= 5
+                if (team_size + 1) <= config.composition.team_size()
                     && target_role.fits_team(team, config)
-                    && team.fits_sr(player_sr, new_average, config.tolerance)
+                    && team.fits_sr(player_sr, new_average, config)
                 {
                     Some(index)
                 } else {
@@ -741,8 +775,10 @@ impl Teams {
             let team2 = &self.0[b];
             let c1 = team1.members_count();
             let c2 = team2.members_count();
+            let repeats1 = config.arena.repeats_with(team1, candidate.index);
+            let repeats2 = config.arena.repeats_with(team2, candidate.index);
 
-            c1.cmp(&c2)
+            c1.cmp(&c2).then(repeats1.cmp(&repeats2))
         });
 
         if let Some(&a) = teams.get(0) {
@@ -752,9 +788,40 @@ impl Teams {
         }
     }
 
+    /// Teams by seed, strongest first.
+    pub fn seeded(&self) -> Vec<&Team> {
+        let mut seeded: Vec<&Team> = self.0.iter().collect();
+        seeded.sort_by(|a, b| b.avg_sr.partial_cmp(&a.avg_sr).unwrap_or(Ordering::Equal));
+        seeded
+    }
+
     pub fn teams_count(&self) -> usize {
         self.0.len()
     }
+
+    pub fn sub_role_duplicates(&self) -> Vec<SubRoleDuplicate> {
+        self.0
+            .iter()
+            .flat_map(|team| {
+                team.duplicate_sub_roles()
+                    .into_iter()
+                    .map(move |(role, sub_role)| SubRoleDuplicate {
+                        team: team.name.clone(),
+                        role,
+                        sub_role,
+                    })
+            })
+            .collect()
+    }
+
+    /// Team split ignoring team order and team uuids, so equivalent results compare equal.
+    pub fn canonical(&self) -> Vec<Vec<(String, SimpleRole)>> {
+        let mut lineups: Vec<Vec<(String, SimpleRole)>> =
+            self.0.iter().map(|team| team.lineup()).collect();
+
+        lineups.sort();
+        lineups
+    }
 }
 
 impl From<PlayerPool> for Teams {
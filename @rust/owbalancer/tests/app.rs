@@ -0,0 +1,543 @@
+use owbalancer::checks::{check_result, Violation};
+use owbalancer::generator::LobbyGenerator;
+use owbalancer::history::TeammateHistory;
+use owbalancer::matchmaking::{BalancerResult, Matchmaking, Preferences, RolePreference};
+use owbalancer::players::{Candidate, Players};
+use owbalancer::roles::SimpleRole;
+use owbalancer::teams::Member;
+use owbalancer::{Adjustment, LobbyOptions, RatingScale};
+use proptest::prelude::*;
+use serde_json::json;
+
+#[derive(Debug, Clone)]
+struct Lobby {
+    generator: LobbyGenerator,
+    tolerance: u32,
+    rank_limiter: bool,
+    duplicate_roles: bool,
+}
+
+fn lobbies() -> impl Strategy<Value = Lobby> {
+    (
+        (10usize..160, any::<u64>()),
+        (0.05f64..0.3, 0.0f64..0.3, 0.0f64..0.3, 0.0f64..1.0),
+        (0u32..200, any::<bool>(), any::<bool>()),
+    )
+        .prop_map(
+            |(
+                (size, seed),
+                (captains, squires, flex, off_role),
+                (tolerance, limiter, duplicates),
+            )| {
+                Lobby {
+                    generator: LobbyGenerator {
+                        size,
+                        captain_share: captains,
+                        squire_share: squires,
+                        flex_share: flex,
+                        off_role_chance: off_role,
+                        seed: Some(seed),
+                        ..LobbyGenerator::default()
+                    },
+                    tolerance,
+                    rank_limiter: limiter,
+                    duplicate_roles: duplicates,
+                }
+            },
+        )
+}
+
+impl Lobby {
+    fn matchmaking<'a>(&self, players: &'a Players) -> Matchmaking<'a> {
+        let mut matchmaking = Matchmaking::new(
+            players,
+            self.tolerance,
+            self.rank_limiter,
+            self.duplicate_roles,
+        );
+        matchmaking.set_seed(self.generator.seed.unwrap_or_default());
+        matchmaking.set_checked(true);
+        matchmaking
+    }
+}
+
+fn balanced(players: &Players) -> BalancerResult {
+    let mut matchmaking = Matchmaking::new(players, 50, false, false);
+    matchmaking.set_seed(1);
+    matchmaking.balance_players();
+    matchmaking.result()
+}
+
+fn lobby() -> Players {
+    LobbyGenerator {
+        size: 60,
+        seed: Some(7),
+        ..LobbyGenerator::default()
+    }
+    .generate()
+}
+
+/// Player who only plays `role`.
+fn one_trick(
+    uuid: &str,
+    role: &str,
+    rank: i32,
+    is_captain: bool,
+    is_squire: bool,
+) -> serde_json::Value {
+    let classes: serde_json::Map<_, _> = ["tank", "dps", "support"]
+        .iter()
+        .map(|&class| {
+            let class_json = json!({
+                "rank": if class == role { rank } else { 0 },
+                "priority": if class == role { 0 } else { 1 },
+                "primary": false,
+                "secondary": false,
+                "isActive": class == role,
+            });
+
+            (class.to_string(), class_json)
+        })
+        .collect();
+
+    json!({
+        "identity": {
+            "uuid": uuid,
+            "name": uuid,
+            "isSquire": is_squire,
+            "isCaptain": is_captain,
+            "isFullFlex": false,
+        },
+        "stats": { "classes": classes },
+        "createdAt": "",
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn balanced_results_keep_invariants(lobby in lobbies(), minimizer in any::<bool>()) {
+        let players = lobby.generator.generate();
+        let mut matchmaking = lobby.matchmaking(&players);
+
+        if minimizer {
+            matchmaking.enable_dispersion_minimizer();
+        }
+
+        matchmaking.balance_players();
+        let result = matchmaking.result();
+
+        prop_assert!(result.violations.is_empty(), "{:?}", result.violations);
+        prop_assert!(lobby.rank_limiter || !result.rank_limiter_relaxed);
+        prop_assert!(lobby.duplicate_roles || !result.duplicate_roles_relaxed);
+    }
+
+    #[test]
+    fn half_and_remaining_keep_invariants(lobby in lobbies()) {
+        let players = lobby.generator.generate();
+        let mut matchmaking = lobby.matchmaking(&players);
+        matchmaking.balance_half();
+        let half = matchmaking.result();
+
+        prop_assert!(half.violations.is_empty(), "{:?}", half.violations);
+
+        let mut matchmaking = lobby.matchmaking(&players);
+        matchmaking.add_reserve(half.leftovers.collect_ids());
+        matchmaking.add_teams(half.teams);
+        matchmaking.balance_remaining();
+        let result = matchmaking.result();
+
+        prop_assert!(result.violations.is_empty(), "{:?}", result.violations);
+    }
+}
+
+#[test]
+fn reports_player_on_two_teams() {
+    let players = lobby();
+    let mut result = balanced(&players);
+    let member = result.teams.0[0].members[1].clone();
+    result.teams.0[1].members.push(member.clone());
+
+    let matchmaking = Matchmaking::new(&players, 50, false, false);
+    let violations = check_result(&players, matchmaking.config(), &result);
+
+    assert!(violations.contains(&Violation::DuplicatePlayer { uuid: member.uuid }));
+}
+
+#[test]
+fn reports_inactive_role_and_stale_rating() {
+    let mut players = lobby();
+    let mut result = balanced(&players);
+    let team = &mut result.teams.0[0];
+    let member = team.members[1].clone();
+    team.total_sr += 1;
+
+    players
+        .0
+        .get_mut(&member.uuid)
+        .unwrap()
+        .stats
+        .classes
+        .get_class_mut(&member.role)
+        .unwrap()
+        .is_active = false;
+
+    let matchmaking = Matchmaking::new(&players, 50, false, false);
+    let violations = check_result(&players, matchmaking.config(), &result);
+
+    assert!(violations.contains(&Violation::InconsistentRating {
+        team: result.teams.0[0].name.clone()
+    }));
+    assert!(violations.contains(&Violation::InactiveRole {
+        uuid: member.uuid,
+        role: member.role
+    }));
+}
+
+#[test]
+fn reports_missing_player() {
+    let players = lobby();
+    let mut result = balanced(&players);
+    let member = result.teams.0[0].members.remove(1);
+
+    let matchmaking = Matchmaking::new(&players, 50, false, false);
+    let violations = check_result(&players, matchmaking.config(), &result);
+
+    assert!(violations.contains(&Violation::MissingPlayer { uuid: member.uuid }));
+}
+
+#[test]
+fn reports_captain_in_leftovers_and_role_over_limit() {
+    let players = lobby();
+    let mut result = balanced(&players);
+    let team = result.teams.0.remove(0);
+    let captain = team.members[0].uuid.clone();
+    let tank = SimpleRole::new("tank");
+
+    for member in &mut result.teams.0[0].members {
+        member.role = tank.clone();
+    }
+
+    result
+        .leftovers
+        .0
+        .push(Candidate::from(players.get_player(&captain).unwrap()));
+
+    let matchmaking = Matchmaking::new(&players, 50, false, false);
+    let violations = check_result(&players, matchmaking.config(), &result);
+
+    assert!(violations.contains(&Violation::LeftoverCaptain { uuid: captain }));
+    assert!(violations.iter().any(
+        |violation| matches!(violation, Violation::RoleOverLimit { role, .. } if *role == tank)
+    ));
+}
+
+#[test]
+fn reports_rank_limit_and_duplicate_sub_roles() {
+    let players = lobby();
+    let mut result = balanced(&players);
+    let is_anchor = |uuid: &str| {
+        let identity = &players.get_player(uuid).unwrap().identity;
+        identity.is_captain || identity.is_squire
+    };
+
+    let (team, role) = result
+        .teams
+        .0
+        .iter()
+        .enumerate()
+        .find_map(|(index, team)| {
+            team.members
+                .iter()
+                .filter(|member| !is_anchor(&member.uuid))
+                .find(|member| {
+                    team.members
+                        .iter()
+                        .filter(|mate| mate.role == member.role && !is_anchor(&mate.uuid))
+                        .count()
+                        > 1
+                })
+                .map(|member| (index, member.role.clone()))
+        })
+        .unwrap();
+
+    for member in &mut result.teams.0[team].members {
+        if member.role == role && !is_anchor(&member.uuid) {
+            member.rank = 100;
+            member.primary = true;
+            member.secondary = false;
+        }
+    }
+
+    let name = result.teams.0[team].name.clone();
+    let relaxed = Matchmaking::new(&players, 50, false, false);
+    let violations = check_result(&players, relaxed.config(), &result);
+
+    assert!(!violations.iter().any(|violation| matches!(
+        violation,
+        Violation::RankLimit { .. } | Violation::DuplicateSubRole { .. }
+    )));
+
+    let strict = Matchmaking::new(&players, 50, true, true);
+    let violations = check_result(&players, strict.config(), &result);
+
+    assert!(violations.contains(&Violation::RankLimit {
+        team: name.clone(),
+        role: role.clone()
+    }));
+    assert!(violations.iter().any(|violation| matches!(
+        violation,
+        Violation::DuplicateSubRole { team, .. } if *team == name
+    )));
+
+    result.rank_limiter_relaxed = true;
+    result.duplicate_roles_relaxed = true;
+    let violations = check_result(&players, strict.config(), &result);
+
+    assert!(!violations.iter().any(|violation| matches!(
+        violation,
+        Violation::RankLimit { .. } | Violation::DuplicateSubRole { .. }
+    )));
+}
+
+#[test]
+fn default_lobby_pairs_squires_by_captain_role() {
+    let players: Players = serde_json::from_value(json!({
+        "tank-captain": one_trick("tank-captain", "tank", 1500, true, false),
+        "dps-captain": one_trick("dps-captain", "dps", 1500, true, false),
+        "support-captain": one_trick("support-captain", "support", 1500, true, false),
+        "tank-squire": one_trick("tank-squire", "tank", 1700, false, true),
+        "support-squire": one_trick("support-squire", "support", 1400, false, true),
+        "second-support-squire": one_trick("second-support-squire", "support", 1600, false, true),
+    }))
+    .unwrap();
+
+    let result = balanced(&players);
+    let squire_role = |captain: &str| {
+        result
+            .teams
+            .0
+            .iter()
+            .find(|team| team.members[0].uuid == captain)
+            .and_then(|team| team.members.get(1))
+            .map(|member| member.role.as_str().to_string())
+    };
+
+    // Tank captains take damage or support, damage captains tank or damage squires and
+    // support captains the leftover one
+    assert_eq!(squire_role("tank-captain").as_deref(), Some("support"));
+    assert_eq!(squire_role("dps-captain").as_deref(), Some("tank"));
+    assert_eq!(squire_role("support-captain").as_deref(), Some("support"));
+}
+
+#[test]
+fn off_role_penalty_spares_flex_players_and_tied_roles() {
+    let class = |rank: i32, priority: i16| {
+        json!({
+            "rank": rank,
+            "priority": priority,
+            "primary": false,
+            "secondary": false,
+            "isActive": true,
+        })
+    };
+    let player = |uuid: &str, is_full_flex: bool| {
+        json!({
+            "identity": {
+                "uuid": uuid,
+                "name": uuid,
+                "isSquire": false,
+                "isCaptain": false,
+                "isFullFlex": is_full_flex,
+            },
+            "stats": {
+                "classes": {
+                    "tank": class(1000, 1),
+                    "dps": class(1000, 1),
+                    "support": class(1000, 2),
+                },
+            },
+            "createdAt": "",
+        })
+    };
+    let mut players: Players = serde_json::from_value(json!({
+        "flex": player("flex", true),
+        "tied": player("tied", false),
+    }))
+    .unwrap();
+
+    players.penalize_off_roles(
+        &Adjustment::Offset { offset: -100 },
+        &RatingScale::default(),
+    );
+
+    let rank = |uuid: &str, role: &str| {
+        players
+            .get_player(uuid)
+            .and_then(|player| player.stats.classes.get_class(&SimpleRole::new(role)))
+            .map(|class| class.rank)
+    };
+
+    assert_eq!(rank("flex", "support"), Some(1000));
+    assert_eq!(rank("tied", "tank"), Some(1000));
+    assert_eq!(rank("tied", "dps"), Some(1000));
+    assert_eq!(rank("tied", "support"), Some(900));
+}
+
+#[test]
+fn candidates_outside_an_arena_compare_by_uuid() {
+    let players = lobby();
+    let mut candidates = players.0.values().take(2).map(Candidate::from);
+    let (first, second) = (candidates.next().unwrap(), candidates.next().unwrap());
+    let copy: Candidate = serde_json::from_value(serde_json::to_value(&first).unwrap()).unwrap();
+
+    assert_ne!(first, second);
+    assert_eq!(first, copy);
+
+    let matchmaking = Matchmaking::new(&players, 50, false, false);
+    let result = balanced(&players);
+    let member: serde_json::Value = serde_json::to_value(&result.teams.0[0].members[0]).unwrap();
+    let member: Member = serde_json::from_value(member).unwrap();
+
+    assert!(!matchmaking.config().arena.is_anchor(member.index));
+}
+
+#[test]
+fn same_lobby_and_seed_give_the_same_teams() {
+    let players = LobbyGenerator {
+        size: 80,
+        seed: Some(11),
+        ..LobbyGenerator::default()
+    }
+    .generate();
+    let options = LobbyOptions {
+        seed: Some(3),
+        ..LobbyOptions::default()
+    };
+    let teams = |attempt: u64| {
+        let mut matchmaking = Matchmaking::new(&players, 100, true, true);
+        options.apply(&mut matchmaking, attempt);
+        matchmaking.enable_dispersion_minimizer();
+        matchmaking.balance_players();
+
+        // Team uuids are new on every run, rosters have to match
+        let rosters: Vec<Vec<(String, SimpleRole)>> = matchmaking
+            .result()
+            .teams
+            .0
+            .iter()
+            .map(|team| {
+                team.members
+                    .iter()
+                    .map(|member| (member.uuid.clone(), member.role.clone()))
+                    .collect()
+            })
+            .collect();
+        rosters
+    };
+
+    assert_eq!(teams(0), teams(0));
+    assert_eq!(teams(4), teams(4));
+}
+
+#[test]
+fn negative_role_weights_are_rejected() {
+    let options = |weight: f64| {
+        serde_json::from_value::<LobbyOptions>(json!({
+            "composition": [
+                { "role": "tank", "slots": 1, "weight": weight },
+                { "role": "dps", "slots": 2 },
+            ],
+        }))
+    };
+
+    assert!(options(1.5).is_ok());
+    assert!(options(0.0).is_ok());
+    assert!(options(-1.0).is_err());
+    assert!(options(1e40).is_err());
+}
+
+#[test]
+fn preference_penalty_saturates() {
+    let placed = |priority: usize| RolePreference {
+        uuid: priority.to_string(),
+        name: priority.to_string(),
+        role: SimpleRole::new("tank"),
+        priority: Some(priority),
+        is_full_flex: false,
+    };
+    let preferences = Preferences {
+        players: vec![placed(1), placed(3)],
+        priority_shares: vec![0.5, 0.0, 0.5],
+        ..Preferences::default()
+    };
+
+    assert_eq!(preferences.penalty(100), 200);
+    assert_eq!(preferences.penalty(u32::MAX), i32::MAX);
+
+    let result = BalancerResult {
+        dispersion: 10,
+        preference_penalty: i32::MAX,
+        ..BalancerResult::default()
+    };
+
+    assert_eq!(result.objective().1, i32::MAX);
+}
+
+#[test]
+fn conservative_balancing_keeps_displayed_ranks() {
+    let original = lobby();
+    let mut players = lobby();
+
+    for class in players
+        .0
+        .values_mut()
+        .flat_map(|player| player.stats.classes.0.values_mut())
+    {
+        class.deviation = Some(100.0);
+    }
+
+    players.apply_conservative(2.0);
+    let result = balanced(&players);
+
+    for team in &result.teams.0 {
+        assert!(team.avg_sr < team.raw_avg_sr);
+
+        for member in &team.members {
+            let class = original
+                .get_player(&member.uuid)
+                .and_then(|player| player.stats.classes.get_class(&member.role))
+                .unwrap();
+
+            assert_eq!(member.rank, class.rank);
+            assert_eq!(member.uncertainty, 200);
+        }
+    }
+
+    let matchmaking = Matchmaking::new(&players, 50, false, false);
+    let member = Member {
+        rank: 150,
+        ..result.teams.0[0].members[0].clone()
+    };
+
+    assert!(check_result(&players, matchmaking.config(), &result).is_empty());
+    assert_eq!(member.strength(matchmaking.config()), 0);
+}
+
+#[test]
+fn mixer_round_reports_broken_tolerance() {
+    let players = lobby();
+    let history = TeammateHistory::default();
+    let tries = || vec![balanced(&players), balanced(&players)];
+    let dispersion = balanced(&players).dispersion;
+
+    let round = BalancerResult::select_round(tries(), dispersion as u32, &history).unwrap();
+    assert_eq!(round.within_tolerance, Some(true));
+
+    let round = BalancerResult::select_round(tries(), 0, &history).unwrap();
+    assert!(dispersion > 0);
+    assert_eq!(round.within_tolerance, Some(false));
+    assert_eq!(balanced(&players).within_tolerance, None);
+    assert!(BalancerResult::select_round(Vec::default(), 0, &history).is_none());
+}
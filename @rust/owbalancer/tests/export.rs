@@ -0,0 +1,139 @@
+use owbalancer::export::{ExportFormat, ExportOptions, Exporter, Platform};
+use owbalancer::matchmaking::BalancerResult;
+use owbalancer::rank_names::{RankError, RankSystemOption};
+use owbalancer::teams::Teams;
+use serde_json::json;
+
+fn member(name: &str, role: &str, rank: i32) -> serde_json::Value {
+    json!({
+        "rank": rank,
+        "uuid": name,
+        "name": name,
+        "primary": false,
+        "secondary": false,
+        "role": role,
+    })
+}
+
+fn result() -> BalancerResult {
+    let teams: Teams = serde_json::from_value(json!([
+        {
+            "uuid": "00000000-0000-0000-0000-000000000001",
+            "avgSr": 2100.0,
+            "name": "*Stars* | [1]",
+            "totalSr": 4200,
+            "members": [member("Ana", "support", 2000), member("Rein", "tank", 2200)],
+        },
+        {
+            "uuid": "00000000-0000-0000-0000-000000000002",
+            "avgSr": 2400.0,
+            "name": "Owls",
+            "totalSr": 4800,
+            "members": [member("Mei_Main", "dps", 2400), member("Zen`", "support", 2400)],
+        },
+    ]))
+    .unwrap();
+
+    BalancerResult {
+        teams,
+        dispersion: 150,
+        ..BalancerResult::default()
+    }
+}
+
+fn exporter(format: ExportFormat, rank_system: Option<&str>) -> Result<Exporter, RankError> {
+    Exporter::new(&ExportOptions {
+        format,
+        rank_system: rank_system.map(|name| RankSystemOption::Preset(name.to_string())),
+    })
+}
+
+#[test]
+fn csv_rows_share_the_header_width() {
+    let csv = exporter(ExportFormat::Csv, None).unwrap().export(&result());
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let header = reader.headers().unwrap().clone();
+    let records: Vec<csv::StringRecord> = reader.records().map(Result::unwrap).collect();
+
+    assert_eq!(
+        header.iter().collect::<Vec<_>>(),
+        vec![
+            "team",
+            "team_average",
+            "dispersion",
+            "player",
+            "role",
+            "rank"
+        ]
+    );
+    assert_eq!(records.len(), 4);
+    assert!(records.iter().all(|record| record.len() == header.len()));
+    assert!(records.iter().all(|record| &record[2] == "150"));
+    // Members are grouped by role, strongest first
+    assert_eq!(&records[0][3], "Ana");
+    assert_eq!(&records[1][3], "Rein");
+}
+
+#[test]
+fn csv_adds_rank_names_for_a_rank_system() {
+    let csv = exporter(ExportFormat::Csv, Some("current"))
+        .unwrap()
+        .export(&result());
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+
+    assert_eq!(reader.headers().unwrap().len(), 7);
+    assert!(reader
+        .records()
+        .all(|record| !record.unwrap()[6].is_empty()));
+}
+
+#[test]
+fn markdown_escapes_team_and_player_names() {
+    let markdown = exporter(ExportFormat::Markdown, None)
+        .unwrap()
+        .export(&result());
+
+    assert!(markdown.contains("### \\*Stars\\* \\| \\[1\\] (average 2100)"));
+    assert!(markdown.contains("| Mei\\_Main | dps | 2400 |"));
+    assert!(markdown.contains("**Dispersion:** 150"));
+}
+
+#[test]
+fn discord_keeps_names_inside_code_blocks() {
+    let discord = exporter(ExportFormat::Discord, None)
+        .unwrap()
+        .export(&result());
+
+    assert!(discord.contains("**\\*Stars\\* \\| \\[1\\]** · average 2100"));
+    assert!(discord.contains("Zen'"));
+    assert_eq!(discord.matches("```").count(), 4);
+}
+
+#[test]
+fn rejects_unknown_rank_systems() {
+    let error = exporter(ExportFormat::Csv, Some("mythic")).err();
+
+    assert_eq!(
+        error,
+        Some(RankError::UnknownPreset {
+            name: String::from("mythic")
+        })
+    );
+}
+
+#[test]
+fn platforms_seed_the_strongest_team_first() {
+    let teams = result().teams;
+    let challonge = Platform::Challonge.export(&teams);
+    let start_gg = Platform::StartGg.export(&teams);
+
+    assert_eq!(
+        challonge.lines().collect::<Vec<_>>(),
+        vec![
+            "name,seed,misc",
+            "Owls,1,\"Mei_Main, Zen`\"",
+            "*Stars* | [1],2,\"Ana, Rein\"",
+        ]
+    );
+    assert!(start_gg.starts_with("Team Name,Seed,Player 1,Player 2\nOwls,1,"));
+}
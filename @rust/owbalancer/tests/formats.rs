@@ -0,0 +1,66 @@
+use owbalancer::formats::{Format, FormatError, Versioned, FORMAT_VERSION};
+use owbalancer::matchmaking::BalancerResult;
+use serde_json::json;
+
+/// Result as saved before formats were versioned.
+fn v1_result() -> serde_json::Value {
+    json!({
+        "teams": [{
+            "uuid": "00000000-0000-0000-0000-000000000000",
+            "avgSr": 2000.0,
+            "name": "team",
+            "totalSr": 4000,
+            "members": [
+                { "rank": 1800, "uuid": "a", "name": "a", "primary": true, "secondary": false, "role": "tank" },
+                { "rank": 2200, "uuid": "b", "name": "b", "primary": false, "secondary": false, "role": "dps" },
+            ],
+        }],
+        "leftovers": [{
+            "uuid": "c",
+            "name": "c",
+            "roles": [{ "Support": [1500, false, true] }],
+            "is_full_flex": false,
+        }],
+        "dispersion": 0,
+    })
+}
+
+#[test]
+fn result_lists_migrate_every_result() {
+    let results: Vec<BalancerResult> = Format::Results
+        .load(json!([v1_result(), v1_result()]))
+        .unwrap();
+
+    assert_eq!(results.len(), 2);
+
+    for result in &results {
+        let role = &result.leftovers.0[0].roles.0[0];
+
+        assert_eq!(result.teams.0[0].raw_avg_sr, 2000.0);
+        assert_eq!(role.role.as_str(), "support");
+        assert_eq!(role.rank, 1500);
+    }
+
+    let saved = serde_json::to_value(Versioned::new(results)).unwrap();
+    let migrated = Format::Results.migrate(saved.clone()).unwrap();
+
+    assert_eq!(saved["version"], json!(FORMAT_VERSION));
+    assert_eq!(migrated, saved["data"]);
+}
+
+#[test]
+fn result_lists_have_to_be_arrays() {
+    assert_eq!(
+        Format::Results.migrate(v1_result()),
+        Err(FormatError::Invalid {
+            message: String::from("results is not an array")
+        })
+    );
+}
+
+#[test]
+fn result_list_schema_describes_an_array() {
+    let schema = serde_json::to_value(Format::Results.schema()).unwrap();
+
+    assert_eq!(schema["properties"]["data"]["type"], json!("array"));
+}
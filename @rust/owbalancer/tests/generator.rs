@@ -0,0 +1,87 @@
+use owbalancer::generator::{LobbyGenerator, RankDistribution};
+use owbalancer::players::Players;
+
+fn generator(seed: u64) -> LobbyGenerator {
+    LobbyGenerator {
+        size: 200,
+        captain_share: 0.2,
+        squire_share: 0.1,
+        flex_share: 0.25,
+        off_role_chance: 0.0,
+        ranks: RankDistribution::Uniform {
+            min: 500,
+            max: 1500,
+        },
+        seed: Some(seed),
+        ..LobbyGenerator::default()
+    }
+}
+
+fn count(players: &Players, filter: impl Fn(&owbalancer::players::Player) -> bool) -> usize {
+    players.0.values().filter(|player| filter(player)).count()
+}
+
+#[test]
+fn same_seed_gives_the_same_lobby() {
+    let lobby = serde_json::to_value(generator(42).generate()).unwrap();
+
+    assert_eq!(
+        lobby,
+        serde_json::to_value(generator(42).generate()).unwrap()
+    );
+    assert_ne!(
+        lobby,
+        serde_json::to_value(generator(43).generate()).unwrap()
+    );
+}
+
+#[test]
+fn respects_captain_squire_and_flex_shares() {
+    let players = generator(7).generate();
+
+    assert_eq!(players.0.len(), 200);
+    assert_eq!(count(&players, |player| player.identity.is_captain), 40);
+    assert_eq!(count(&players, |player| player.identity.is_squire), 20);
+    assert_eq!(
+        count(&players, |player| player.identity.is_captain
+            && player.identity.is_squire),
+        0
+    );
+
+    let flex = count(&players, |player| {
+        player.identity.is_full_flex == Some(true)
+    });
+    assert!((30..=70).contains(&flex), "{} flex players", flex);
+
+    for player in players.0.values() {
+        let classes = &player.stats.classes.0;
+        let active = classes.values().filter(|class| class.is_active).count();
+        let preferred = classes.values().find(|class| class.priority == 1).unwrap();
+
+        // Without off roles only flex players play more than their preferred role
+        assert!(preferred.is_active);
+        assert_eq!(
+            active == classes.len(),
+            player.identity.is_full_flex == Some(true)
+        );
+        assert!(classes
+            .values()
+            .filter(|class| class.is_active)
+            .all(|class| (500..=1500).contains(&class.rank)));
+    }
+}
+
+#[test]
+fn shares_are_clamped_to_the_lobby() {
+    let players = LobbyGenerator {
+        size: 10,
+        captain_share: 0.8,
+        squire_share: 0.8,
+        seed: Some(1),
+        ..LobbyGenerator::default()
+    }
+    .generate();
+
+    assert_eq!(count(&players, |player| player.identity.is_captain), 8);
+    assert_eq!(count(&players, |player| player.identity.is_squire), 2);
+}
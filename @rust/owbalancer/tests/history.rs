@@ -0,0 +1,46 @@
+use owbalancer::history::TeammateHistory;
+use owbalancer::teams::Teams;
+use serde_json::json;
+
+/// One event with a single team of the given players.
+fn event(uuids: &[&str]) -> Teams {
+    let members: Vec<serde_json::Value> = uuids
+        .iter()
+        .map(|uuid| {
+            json!({
+                "rank": 1000,
+                "uuid": uuid,
+                "name": uuid,
+                "primary": false,
+                "secondary": false,
+                "role": "dps",
+            })
+        })
+        .collect();
+
+    serde_json::from_value(json!([{
+        "uuid": "00000000-0000-0000-0000-000000000000",
+        "avgSr": 1000.0,
+        "name": "team",
+        "totalSr": 0,
+        "members": members,
+    }]))
+    .unwrap()
+}
+
+#[test]
+fn recent_teammates_weigh_more() {
+    let rosters = [event(&["a", "b"]), event(&["c", "d"]), event(&["a", "c"])];
+    let mut history = TeammateHistory::from(&rosters[..]);
+
+    assert_eq!(history.events, 3);
+    assert_eq!(history.count("a", "b"), 1);
+    assert_eq!(history.count("c", "d"), 2);
+    assert_eq!(history.count("a", "c"), 3);
+    assert_eq!(history.count("c", "a"), 3);
+
+    history.add_teams(&event(&["a", "b"]));
+
+    assert_eq!(history.count("a", "b"), 5);
+    assert_eq!(history.total_repeats(&event(&["a", "b", "c"])), 8);
+}
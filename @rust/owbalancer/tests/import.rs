@@ -0,0 +1,141 @@
+use owbalancer::import::{ColumnMapping, ImportErrorKind, ImportResult};
+use owbalancer::players::Player;
+use owbalancer::roles::SimpleRole;
+use serde_json::json;
+
+fn mapping(delimiter: char) -> ColumnMapping {
+    serde_json::from_value(json!({
+        "name": "Name",
+        "captain": "Captain",
+        "delimiter": delimiter,
+        "roles": [
+            { "role": "tank", "rank": "Tank" },
+            { "role": "dps", "rank": "Damage", "priority": "Damage priority" },
+        ],
+    }))
+    .unwrap()
+}
+
+fn player<'a>(result: &'a ImportResult, name: &str) -> &'a Player {
+    result
+        .players
+        .0
+        .values()
+        .find(|player| player.identity.name == name)
+        .unwrap()
+}
+
+#[test]
+fn imports_ranks_names_and_flags() {
+    let csv = "Name,Captain,Tank,Damage,Damage priority\n\
+               Ana, yes ,2500,Diamond 3,\n\
+               Mei,,,1800,1\n";
+    let result = ImportResult::from_csv(csv, &mapping(','));
+
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert_eq!(result.players.0.len(), 2);
+
+    let ana = player(&result, "Ana");
+    let tank = ana
+        .stats
+        .classes
+        .get_class(&SimpleRole::new("tank"))
+        .unwrap();
+    let dps = ana
+        .stats
+        .classes
+        .get_class(&SimpleRole::new("dps"))
+        .unwrap();
+
+    assert!(ana.identity.is_captain);
+    assert!(tank.is_active);
+    assert_eq!(tank.rank, 2500);
+    assert!(dps.rank > 0);
+    // Priorities follow the mapping order and start at 1
+    assert_eq!((tank.priority, dps.priority), (1, 2));
+
+    let mei = player(&result, "Mei");
+    let tank = mei
+        .stats
+        .classes
+        .get_class(&SimpleRole::new("tank"))
+        .unwrap();
+
+    assert!(!mei.identity.is_captain);
+    assert!(!tank.is_active);
+    assert_eq!(
+        mei.stats
+            .classes
+            .get_class(&SimpleRole::new("dps"))
+            .unwrap()
+            .priority,
+        1
+    );
+}
+
+#[test]
+fn reports_and_skips_invalid_rows() {
+    let csv = "Name,Captain,Tank,Damage,Damage priority\n\
+               ,,2500,,\n\
+               Ana,maybe,2500,,\n\
+               Mei,,lots,,\n\
+               Zen,,2500,,\n";
+    let result = ImportResult::from_csv(csv, &mapping(','));
+    let errors: Vec<(usize, &ImportErrorKind)> = result
+        .errors
+        .iter()
+        .map(|error| (error.row, &error.error))
+        .collect();
+
+    assert_eq!(result.players.0.len(), 1);
+    assert_eq!(errors.len(), 3);
+    assert_eq!(errors[0], (2, &ImportErrorKind::EmptyName));
+    assert!(matches!(
+        errors[1],
+        (3, ImportErrorKind::InvalidFlag { .. })
+    ));
+    assert!(matches!(
+        errors[2],
+        (4, ImportErrorKind::InvalidRank { .. })
+    ));
+}
+
+#[test]
+fn reads_other_ascii_delimiters() {
+    let csv = "Name;Captain;Tank;Damage;Damage priority\nAna;;2500;;\n";
+    let result = ImportResult::from_csv(csv, &mapping(';'));
+
+    assert!(result.errors.is_empty(), "{:?}", result.errors);
+    assert_eq!(result.players.0.len(), 1);
+}
+
+#[test]
+fn rejects_non_ascii_delimiters() {
+    let result = ImportResult::from_csv("Name§Tank\nAna§2500\n", &mapping('§'));
+
+    assert!(result.players.0.is_empty());
+    assert_eq!(
+        result.errors[0].error,
+        ImportErrorKind::InvalidDelimiter { delimiter: '§' }
+    );
+}
+
+#[test]
+fn reports_missing_and_duplicate_columns() {
+    let csv = "Name,Tank,Damage,Damage priority,Tank\nAna,2500,,,2600\n";
+    let result = ImportResult::from_csv(csv, &mapping(','));
+    let errors: Vec<(&str, &ImportErrorKind)> = result
+        .errors
+        .iter()
+        .map(|error| (error.column.as_str(), &error.error))
+        .collect();
+
+    assert!(result.players.0.is_empty());
+    assert_eq!(
+        errors,
+        vec![
+            ("Captain", &ImportErrorKind::MissingColumn),
+            ("Tank", &ImportErrorKind::DuplicateColumn),
+        ]
+    );
+}
@@ -0,0 +1,253 @@
+use owbalancer::pairing::{Bracket, BracketMatch, PairingOptions, Round, Schedule, Slot};
+use owbalancer::teams::Teams;
+use serde_json::json;
+use std::collections::HashSet;
+
+/// Teams named by seed, `t0` being the strongest.
+fn teams(count: usize) -> Teams {
+    let teams: Vec<serde_json::Value> = (0..count)
+        .map(|seed| {
+            json!({
+                "uuid": format!("00000000-0000-0000-0000-{:012}", seed),
+                "avgSr": 3000.0 - seed as f32 * 100.0,
+                "name": format!("t{}", seed),
+                "totalSr": 0,
+                "members": [],
+            })
+        })
+        .collect();
+
+    serde_json::from_value(json!(teams)).unwrap()
+}
+
+fn uuid(teams: &Teams, seed: usize) -> String {
+    teams.0[seed].uuid.to_string()
+}
+
+fn matches(round: &Round) -> Vec<(String, String)> {
+    round
+        .pairings
+        .iter()
+        .filter_map(|pairing| Some((pairing.home.clone(), pairing.away.clone()?)))
+        .collect()
+}
+
+fn elimination(teams: &Teams, double: bool) -> Bracket {
+    match Schedule::new(teams, &PairingOptions::Elimination { double }) {
+        Schedule::Elimination { bracket } => bracket,
+        _ => unreachable!(),
+    }
+}
+
+fn swiss(
+    teams: &Teams,
+    number: usize,
+    played: &[(usize, usize)],
+    points: &[(usize, f32)],
+) -> Round {
+    let options = PairingOptions::Swiss {
+        number,
+        played: played
+            .iter()
+            .map(|&(a, b)| (uuid(teams, a), uuid(teams, b)))
+            .collect(),
+        points: points
+            .iter()
+            .map(|&(team, points)| (uuid(teams, team), points))
+            .collect(),
+        byes: Vec::default(),
+    };
+
+    match Schedule::new(teams, &options) {
+        Schedule::Swiss { round } => round,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn round_robin_pairs_every_team_once() {
+    for count in 2..10 {
+        let teams = teams(count);
+        let groups = match Schedule::new(&teams, &PairingOptions::RoundRobin { groups: 1 }) {
+            Schedule::RoundRobin { groups } => groups,
+            _ => unreachable!(),
+        };
+        let rounds = &groups[0].rounds;
+        let mut met = HashSet::new();
+
+        assert_eq!(rounds.len(), count + count % 2 - 1);
+
+        for round in rounds {
+            let mut playing = HashSet::new();
+
+            for pairing in &round.pairings {
+                assert!(playing.insert(pairing.home.clone()));
+
+                if let Some(away) = &pairing.away {
+                    assert!(playing.insert(away.clone()));
+
+                    let mut pair = [pairing.home.clone(), away.clone()];
+                    pair.sort();
+                    assert!(met.insert(pair), "{} teams meet twice", count);
+                }
+            }
+
+            assert_eq!(playing.len(), count);
+        }
+
+        assert_eq!(met.len(), count * (count - 1) / 2);
+    }
+}
+
+#[test]
+fn round_robin_groups_are_snake_seeded() {
+    let teams = teams(8);
+    let groups = match Schedule::new(&teams, &PairingOptions::RoundRobin { groups: 2 }) {
+        Schedule::RoundRobin { groups } => groups,
+        _ => unreachable!(),
+    };
+    let seeds = |group: usize| -> Vec<String> {
+        groups[group]
+            .teams
+            .iter()
+            .map(|team| {
+                teams
+                    .0
+                    .iter()
+                    .find(|t| t.uuid.to_string() == *team)
+                    .unwrap()
+                    .name
+                    .clone()
+            })
+            .collect()
+    };
+
+    assert_eq!(seeds(0), vec!["t0", "t3", "t4", "t7"]);
+    assert_eq!(seeds(1), vec!["t1", "t2", "t5", "t6"]);
+}
+
+#[test]
+fn swiss_backtracks_to_avoid_rematches() {
+    let teams = teams(4);
+    // Pairing t0 with its closest opponent t1 would leave t2 and t3 with a rematch
+    let round = swiss(&teams, 3, &[(0, 2), (2, 3)], &[]);
+    let matches = matches(&round);
+
+    assert_eq!(round.number, 3);
+    assert_eq!(round.rematches, 0);
+    assert!(matches.contains(&(uuid(&teams, 0), uuid(&teams, 3))));
+    assert!(matches.contains(&(uuid(&teams, 1), uuid(&teams, 2))));
+}
+
+#[test]
+fn swiss_pairs_by_points_then_rating() {
+    let teams = teams(4);
+    let round = swiss(&teams, 2, &[(0, 1), (2, 3)], &[(0, 1.0), (2, 1.0)]);
+    let matches = matches(&round);
+
+    assert_eq!(round.rematches, 0);
+    assert!(matches.contains(&(uuid(&teams, 0), uuid(&teams, 2))));
+    assert!(matches.contains(&(uuid(&teams, 1), uuid(&teams, 3))));
+}
+
+#[test]
+fn swiss_reports_forced_rematches() {
+    let teams = teams(2);
+    let round = swiss(&teams, 2, &[(0, 1)], &[]);
+
+    assert_eq!(round.rematches, 1);
+    assert_eq!(matches(&round).len(), 1);
+}
+
+#[test]
+fn elimination_follows_the_seed_order() {
+    let teams = teams(6);
+    let bracket = elimination(&teams, false);
+    let pairs: Vec<(String, Option<String>)> = bracket
+        .first_round
+        .pairings
+        .iter()
+        .map(|pairing| (pairing.home.clone(), pairing.away.clone()))
+        .collect();
+
+    assert_eq!(bracket.size, 8);
+    // 0-7, 3-4, 1-6, 2-5 with the two top seeds on a bye
+    assert_eq!(
+        pairs,
+        vec![
+            (uuid(&teams, 0), None),
+            (uuid(&teams, 3), Some(uuid(&teams, 4))),
+            (uuid(&teams, 1), None),
+            (uuid(&teams, 2), Some(uuid(&teams, 5))),
+        ]
+    );
+    assert!(!bracket.double);
+    assert!(bracket.losers_rounds.is_empty());
+    assert!(bracket.grand_final.is_none());
+}
+
+#[test]
+fn double_elimination_drops_losers_down() {
+    let bracket = elimination(&teams(8), true);
+    let winners_loser = |round, index| Some(Slot::WinnersLoser { round, index });
+    let losers_winner = |round, index| Some(Slot::LosersWinner { round, index });
+    let game = |home, away| BracketMatch { home, away };
+    let rounds: Vec<&Vec<BracketMatch>> = bracket
+        .losers_rounds
+        .iter()
+        .map(|round| &round.matches)
+        .collect();
+
+    assert!(bracket.double);
+    assert_eq!(bracket.first_round.pairings.len(), 4);
+    assert_eq!(
+        rounds,
+        vec![
+            // First round losers meet their neighbours
+            &vec![
+                game(winners_loser(1, 0), winners_loser(1, 1)),
+                game(winners_loser(1, 2), winners_loser(1, 3)),
+            ],
+            // Second round losers drop down in reverse
+            &vec![
+                game(losers_winner(1, 0), winners_loser(2, 1)),
+                game(losers_winner(1, 1), winners_loser(2, 0)),
+            ],
+            &vec![game(losers_winner(2, 0), losers_winner(2, 1))],
+            &vec![game(losers_winner(3, 0), winners_loser(3, 0))],
+        ]
+    );
+    assert_eq!(
+        bracket.grand_final,
+        Some(game(
+            Some(Slot::WinnersWinner { round: 3, index: 0 }),
+            losers_winner(4, 0)
+        ))
+    );
+}
+
+#[test]
+fn double_elimination_leaves_bye_slots_empty() {
+    let bracket = elimination(&teams(6), true);
+    let first = &bracket.losers_rounds[0].matches;
+
+    // Seeds 0 and 1 have a bye in the first and third match, nobody drops down from them
+    assert_eq!(first[0].home, None);
+    assert_eq!(
+        first[0].away,
+        Some(Slot::WinnersLoser { round: 1, index: 1 })
+    );
+    assert_eq!(first[1].home, None);
+    assert_eq!(
+        first[1].away,
+        Some(Slot::WinnersLoser { round: 1, index: 3 })
+    );
+
+    let pair = elimination(&teams(2), true);
+
+    assert!(pair.losers_rounds.is_empty());
+    assert_eq!(
+        pair.grand_final.unwrap().away,
+        Some(Slot::WinnersLoser { round: 1, index: 0 })
+    );
+}
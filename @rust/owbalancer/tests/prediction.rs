@@ -0,0 +1,55 @@
+use owbalancer::prediction::Predictor;
+use owbalancer::teams::Teams;
+use serde_json::json;
+
+fn teams(ratings: &[f32]) -> Teams {
+    let teams: Vec<serde_json::Value> = ratings
+        .iter()
+        .enumerate()
+        .map(|(index, rating)| {
+            json!({
+                "uuid": format!("00000000-0000-0000-0000-{:012}", index),
+                "avgSr": rating,
+                "name": format!("t{}", index),
+                "totalSr": 0,
+                "members": [],
+            })
+        })
+        .collect();
+
+    serde_json::from_value(json!(teams)).unwrap()
+}
+
+#[test]
+fn scale_gap_means_ten_to_one_odds() {
+    let predictions = Predictor::default().predict(&teams(&[1900.0, 1500.0]));
+
+    assert!((predictions.matrix[0][1] - 10.0 / 11.0).abs() < 1e-9);
+    assert!((predictions.matrix[1][0] - 1.0 / 11.0).abs() < 1e-9);
+    assert_eq!(
+        predictions.vs_field,
+        vec![predictions.matrix[0][1], predictions.matrix[1][0]]
+    );
+}
+
+#[test]
+fn non_positive_scales_give_probabilities() {
+    for scale in [0.0, -400.0, f64::NAN].iter() {
+        let predictor = Predictor { scale: *scale };
+        let predictions = predictor.predict(&teams(&[1500.0, 1500.0, 1400.0]));
+
+        for row in &predictions.matrix {
+            for chance in row {
+                assert!(
+                    (0.0..=1.0).contains(chance),
+                    "{} for scale {}",
+                    chance,
+                    scale
+                );
+            }
+        }
+
+        assert_eq!(predictions.matrix[0][1], 0.5);
+        assert!(predictions.matrix[0][2] > 0.99);
+    }
+}
@@ -0,0 +1,105 @@
+use owbalancer::rank_names::{RankError, RankName, RankSystem, RankSystemOption};
+use serde_json::json;
+
+fn systems() -> Vec<RankSystem> {
+    vec![RankSystem::current(), RankSystem::legacy()]
+}
+
+/// Every rank name of the system, from the lowest.
+fn names(system: &RankSystem) -> Vec<RankName> {
+    system
+        .tiers
+        .iter()
+        .flat_map(|tier| {
+            let divisions: Vec<Option<u8>> = if tier.divisions <= 1 {
+                vec![None]
+            } else {
+                (1..=tier.divisions).rev().map(Some).collect()
+            };
+
+            divisions.into_iter().map(move |division| RankName {
+                tier: tier.name.clone(),
+                division,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn names_parse_back_to_themselves() {
+    for system in systems() {
+        let mut previous = -1;
+
+        for name in names(&system) {
+            let rating = system.parse(&name.to_string()).unwrap();
+
+            assert!(rating > previous, "{} is not above the rank below", name);
+            assert_eq!(system.name(rating), Some(name.clone()));
+            previous = rating;
+        }
+    }
+}
+
+#[test]
+fn ratings_name_the_division_they_fall_into() {
+    for system in systems() {
+        for rating in (0..system.top).step_by(7) {
+            let name = system.name(rating).unwrap();
+            let start = system.parse(&name.to_string()).unwrap();
+
+            assert!(start <= rating, "{} starts above {}", name, rating);
+            assert_eq!(system.name(start), Some(name));
+        }
+    }
+}
+
+#[test]
+fn parses_case_insensitive_names_and_aliases() {
+    let system = RankSystem::current();
+    let gold = system.parse("Gold 3").unwrap();
+
+    assert_eq!(system.parse("gold 3"), Ok(gold));
+    assert_eq!(system.parse("GOLD3"), Ok(gold));
+    assert_eq!(system.parse("GM 1"), system.parse("grandmaster 1"));
+    assert_eq!(system.parse(" 1234 "), Ok(1234));
+    assert_eq!(system.parse("Diamond"), system.parse("Diamond 5"));
+
+    let custom: RankSystem = serde_json::from_value(json!({
+        "tiers": [
+            { "name": "Rookie", "start": 0, "divisions": 1, "aliases": ["NEW"] },
+            { "name": "Pro", "start": 1000, "divisions": 2, "aliases": ["Elite"] },
+        ],
+        "top": 2000,
+    }))
+    .unwrap();
+
+    assert_eq!(custom.parse("new"), Ok(0));
+    assert_eq!(custom.parse("elite 1"), Ok(1500));
+    assert_eq!(custom.parse("ELITE 2"), Ok(1000));
+}
+
+#[test]
+fn reports_unknown_tiers_and_divisions() {
+    let system = RankSystem::current();
+
+    assert_eq!(
+        system.parse("Mythic 2"),
+        Err(RankError::UnknownTier {
+            tier: String::from("mythic")
+        })
+    );
+    assert_eq!(
+        system.parse("Gold 6"),
+        Err(RankError::InvalidDivision {
+            division: String::from("6")
+        })
+    );
+    assert_eq!(
+        RankSystemOption::Preset(String::from("next"))
+            .system()
+            .err(),
+        Some(RankError::UnknownPreset {
+            name: String::from("next")
+        })
+    );
+}
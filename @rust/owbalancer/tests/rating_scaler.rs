@@ -0,0 +1,69 @@
+use owbalancer::players::Players;
+use owbalancer::rating_scaler::{CurveError, RatingScaler};
+use owbalancer::{AdjustSr, Adjustment, BalanceError, LobbyOptions, Point, RatingScale};
+use serde_json::json;
+
+fn adjust(top_rating: f64, max_adjustment: f64) -> AdjustSr {
+    serde_json::from_value(json!({
+        "isEnabled": true,
+        "scale": { "topRating": top_rating, "maxAdjustment": max_adjustment },
+        "tank": {
+            "any": { "offset": 0 },
+            "primary": { "percent": 5 },
+            "secondary": { "linear": [{ "x": 0, "y": 0 }, { "x": 1, "y": 1 }] },
+        },
+    }))
+    .unwrap()
+}
+
+#[test]
+fn reports_scale_without_a_role() {
+    let issues = RatingScaler::validate(&adjust(0.0, -10.0));
+    let errors: Vec<&CurveError> = issues
+        .iter()
+        .filter(|issue| issue.role.is_none() && issue.specialization.is_none())
+        .map(|issue| &issue.error)
+        .collect();
+
+    assert_eq!(issues.len(), 2);
+    assert_eq!(
+        errors,
+        vec![
+            &CurveError::InvalidTopRating { top_rating: 0.0 },
+            &CurveError::InvalidMaxAdjustment {
+                max_adjustment: -10.0
+            },
+        ]
+    );
+}
+
+#[test]
+fn accepts_valid_scale() {
+    assert!(RatingScaler::validate(&adjust(5000.0, 0.0)).is_empty());
+}
+
+#[test]
+fn malformed_off_role_penalty_is_rejected() {
+    let penalty = Adjustment::Linear {
+        linear: vec![Point { x: 0.5, y: -0.1 }],
+    };
+    let issues = RatingScaler::validate_penalty(&penalty, &RatingScale::default());
+
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0].role.is_none());
+    assert_eq!(issues[0].specialization.as_deref(), Some("offRole"));
+    assert_eq!(issues[0].error, CurveError::TooFewPoints { count: 1 });
+
+    let options = LobbyOptions {
+        off_role_penalty: Some(penalty),
+        ..LobbyOptions::default()
+    };
+    let result = options.adjust_players(&mut Players::default(), adjust(2000.0, 50.0));
+
+    assert!(matches!(result, Err(BalanceError::InvalidCurves { .. })));
+    assert!(RatingScaler::validate_penalty(
+        &Adjustment::Percent { percent: -10.0 },
+        &RatingScale::default()
+    )
+    .is_empty());
+}
@@ -0,0 +1,199 @@
+use owbalancer::players::Players;
+use owbalancer::ratings::{Glicko2, Glicko2Game, MatchResult, RatingError, RatingModel};
+use owbalancer::teams::Team;
+use serde_json::json;
+
+const GLICKO_SCALE: f64 = 173.7178;
+
+fn game(rating: &Glicko2, opponent: (f64, f64), score: f64) -> Glicko2Game {
+    Glicko2Game {
+        own_mu: rating.mu,
+        opponent_mu: (opponent.0 - 1500.0) / GLICKO_SCALE,
+        opponent_phi: opponent.1 / GLICKO_SCALE,
+        score,
+    }
+}
+
+/// Players `home` and `away` on tank with the given ranks.
+fn players(home: i32, away: i32) -> Players {
+    let player = |uuid: &str, rank: i32| {
+        json!({
+            "identity": {
+                "uuid": uuid,
+                "name": uuid,
+                "isSquire": false,
+                "isCaptain": false,
+                "isFullFlex": false,
+            },
+            "stats": {
+                "classes": {
+                    "tank": {
+                        "rank": rank,
+                        "priority": 0,
+                        "primary": false,
+                        "secondary": false,
+                        "isActive": true,
+                    },
+                },
+            },
+            "createdAt": "",
+        })
+    };
+
+    serde_json::from_value(json!({
+        "home": player("home", home),
+        "away": player("away", away),
+    }))
+    .unwrap()
+}
+
+fn team(uuid: &str) -> Team {
+    serde_json::from_value(json!({
+        "uuid": "00000000-0000-0000-0000-000000000000",
+        "avgSr": 0.0,
+        "name": uuid,
+        "totalSr": 0,
+        "members": [{
+            "rank": 0,
+            "uuid": uuid,
+            "name": uuid,
+            "primary": false,
+            "secondary": false,
+            "role": "tank",
+        }],
+    }))
+    .unwrap()
+}
+
+fn played(home_score: f64, away_score: f64) -> MatchResult {
+    MatchResult {
+        home: team("home"),
+        away: team("away"),
+        home_score,
+        away_score,
+    }
+}
+
+fn rank(players: &Players, uuid: &str) -> i32 {
+    players.0[uuid]
+        .stats
+        .classes
+        .0
+        .values()
+        .next()
+        .unwrap()
+        .rank
+}
+
+fn elo() -> RatingModel {
+    serde_json::from_value(json!({ "model": "elo", "kFactor": 32.0 })).unwrap()
+}
+
+fn glicko2() -> RatingModel {
+    serde_json::from_value(json!({ "model": "glicko2" })).unwrap()
+}
+
+#[test]
+fn glicko2_matches_the_paper_example() {
+    // Section "Example calculation" of Glickman's Glicko-2 paper
+    let rating = Glicko2 {
+        mu: 0.0,
+        phi: 200.0 / GLICKO_SCALE,
+        sigma: 0.06,
+    };
+    let games = [
+        game(&rating, (1400.0, 30.0), 1.0),
+        game(&rating, (1550.0, 100.0), 0.0),
+        game(&rating, (1700.0, 300.0), 0.0),
+    ];
+    let rating = rating.rate(&games, 0.5);
+
+    assert!((rating.mu * GLICKO_SCALE + 1500.0 - 1464.06).abs() < 0.01);
+    assert!((rating.phi * GLICKO_SCALE - 151.52).abs() < 0.01);
+    assert!((rating.sigma - 0.05999).abs() < 0.00001);
+}
+
+#[test]
+fn glicko2_keeps_ratings_finite_for_certain_results() {
+    let mut players = players(10_000, 0);
+    glicko2()
+        .update(&mut players, &[played(1.0, 0.0), played(0.0, 1.0)])
+        .unwrap();
+
+    let class = players.0["home"].stats.classes.0.values().next().unwrap();
+
+    assert!(class.deviation.unwrap().is_finite());
+    assert!(class.volatility.unwrap().is_finite());
+    assert!(rank(&players, "home") > 5_000);
+    assert!(rank(&players, "away") < 5_000);
+}
+
+#[test]
+fn elo_moves_both_teams_by_the_same_amount() {
+    let mut players = players(1500, 1500);
+    elo().update(&mut players, &[played(2.0, 0.0)]).unwrap();
+
+    assert_eq!(rank(&players, "home"), 1516);
+    assert_eq!(rank(&players, "away"), 1484);
+}
+
+#[test]
+fn elo_is_symmetric_in_home_and_away() {
+    let mut home_won = players(1600, 1400);
+    elo().update(&mut home_won, &[played(3.0, 1.0)]).unwrap();
+
+    let mut away_won = players(1400, 1600);
+    elo().update(&mut away_won, &[played(1.0, 3.0)]).unwrap();
+
+    assert_eq!(rank(&home_won, "home"), rank(&away_won, "away"));
+    assert_eq!(rank(&home_won, "away"), rank(&away_won, "home"));
+    assert_eq!(rank(&home_won, "home") + rank(&home_won, "away"), 3000);
+}
+
+#[test]
+fn elo_draw_between_equals_changes_nothing() {
+    let mut players = players(1500, 1500);
+    elo()
+        .update(&mut players, &[played(0.0, 0.0), played(1.0, 1.0)])
+        .unwrap();
+
+    assert_eq!(rank(&players, "home"), 1500);
+    assert_eq!(rank(&players, "away"), 1500);
+}
+
+#[test]
+fn invalid_models_leave_ratings_alone() {
+    let glicko2 = |tau: f64, scale: f64| RatingModel::Glicko2 {
+        scale,
+        tau,
+        deviation: 350.0,
+        volatility: 0.06,
+    };
+    let invalid = [
+        (glicko2(-0.5, 400.0), RatingError::InvalidTau { tau: -0.5 }),
+        (glicko2(0.0, 400.0), RatingError::InvalidTau { tau: 0.0 }),
+        (glicko2(0.5, 0.0), RatingError::InvalidScale { scale: 0.0 }),
+        (
+            RatingModel::Elo {
+                k_factor: 32.0,
+                scale: 0.0,
+            },
+            RatingError::InvalidScale { scale: 0.0 },
+        ),
+        (
+            RatingModel::Elo {
+                k_factor: -1.0,
+                scale: 400.0,
+            },
+            RatingError::InvalidKFactor { k_factor: -1.0 },
+        ),
+    ];
+
+    for (model, error) in invalid {
+        let mut players = players(1500, 1500);
+
+        assert_eq!(model.update(&mut players, &[played(1.0, 0.0)]), Err(error));
+        assert_eq!(rank(&players, "home"), 1500);
+        assert_eq!(rank(&players, "away"), 1500);
+    }
+}
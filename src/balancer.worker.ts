@@ -1,5 +1,21 @@
 import * as Comlink from 'comlink';
-import init, { balance, balance_half, balance_final } from '@rust/owbalancer';
+import init, {
+  balance,
+  balance_mixer,
+  balance_half,
+  balance_final,
+  export_result,
+  export_teams,
+  format_schema,
+  generate_lobby,
+  import_players,
+  migrate_format,
+  pair_teams,
+  parse_rank,
+  rank_name,
+  preview_adjust_sr,
+  update_ratings,
+} from '@rust/owbalancer';
 import wasmFileUrl from '@rust/owbalancer/owbalancer_bg.wasm?url';
 
 export default class WasmWorker {
@@ -32,6 +48,9 @@ export default class WasmWorker {
       disableType,
       dispersionMinimizer,
       triesCount,
+      resultsCount,
+      minDifference,
+      lobbyOptions,
     } = JSON.parse(data);
 
     return new Promise((resolve) => {
@@ -43,7 +62,10 @@ export default class WasmWorker {
         adjustSr,
         disableType,
         dispersionMinimizer,
-        triesCount
+        triesCount,
+        resultsCount || 0,
+        minDifference || 0,
+        lobbyOptions
       );
 
       resolve(r);
@@ -51,9 +73,12 @@ export default class WasmWorker {
   }
 
   halfBalance(data) {
-    const { players, range, lowRankLimiter, disallowSecondaryRoles, adjustSr } = JSON.parse(data);
+    const { players, range, lowRankLimiter, disallowSecondaryRoles, adjustSr, lobbyOptions } =
+      JSON.parse(data);
     return new Promise((resolve) => {
-      resolve(balance_half(players, range, lowRankLimiter, disallowSecondaryRoles, adjustSr));
+      resolve(
+        balance_half(players, range, lowRankLimiter, disallowSecondaryRoles, adjustSr, lobbyOptions)
+      );
     });
   }
 
@@ -66,6 +91,7 @@ export default class WasmWorker {
       reserveCopy,
       teamsCopy,
       adjustSr,
+      lobbyOptions,
     } = JSON.parse(data);
     return new Promise((resolve) => {
       resolve(
@@ -76,11 +102,120 @@ export default class WasmWorker {
           disallowSecondaryRoles,
           reserveCopy,
           teamsCopy,
-          adjustSr
+          adjustSr,
+          lobbyOptions
+        )
+      );
+    });
+  }
+
+  mixerBalance(data) {
+    const {
+      players,
+      range,
+      lowRankLimiter,
+      disallowSecondaryRoles,
+      adjustSr,
+      disableType,
+      dispersionMinimizer,
+      triesCount,
+      rounds,
+      lobbyOptions,
+    } = JSON.parse(data);
+
+    return new Promise((resolve) => {
+      resolve(
+        balance_mixer(
+          players,
+          range,
+          lowRankLimiter,
+          disallowSecondaryRoles,
+          adjustSr,
+          disableType,
+          dispersionMinimizer,
+          triesCount,
+          rounds,
+          lobbyOptions
         )
       );
     });
   }
+
+  exportResult(data) {
+    const { result, exportOptions } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(export_result(result, exportOptions));
+    });
+  }
+
+  exportTeams(data) {
+    const { teams, platform } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(export_teams(teams, platform));
+    });
+  }
+
+  migrateFormat(data) {
+    const { format, value } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(migrate_format(format, value));
+    });
+  }
+
+  formatSchema(format) {
+    return new Promise((resolve) => {
+      resolve(format_schema(format));
+    });
+  }
+
+  generateLobby(data) {
+    const { generatorOptions } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(generate_lobby(generatorOptions));
+    });
+  }
+
+  importPlayers(data) {
+    const { csv, columnMapping } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(import_players(csv, columnMapping));
+    });
+  }
+
+  parseRank(data) {
+    const { rank, rankSystem } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(parse_rank(rank, rankSystem));
+    });
+  }
+
+  rankName(data) {
+    const { rating, rankSystem } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(rank_name(rating, rankSystem));
+    });
+  }
+
+  pairTeams(data) {
+    const { teams, pairingOptions } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(pair_teams(teams, pairingOptions));
+    });
+  }
+
+  updateRatings(data) {
+    const { players, matches, ratingModel } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(update_ratings(players, matches, ratingModel));
+    });
+  }
+
+  previewAdjustSr(data) {
+    const { adjustSr, step } = JSON.parse(data);
+    return new Promise((resolve) => {
+      resolve(preview_adjust_sr(adjustSr, step));
+    });
+  }
 }
 
 Comlink.expose(WasmWorker);
@@ -1,8 +1,62 @@
 import { Teams } from '@/objects/team';
 import { Points } from '@/objects/bezier';
 
+export type SubRole =
+  | 'any'
+  | 'mainTank'
+  | 'offTank'
+  | 'hitscan'
+  | 'projectile'
+  | 'mainHeal'
+  | 'lightHeal'
+  | 'primary'
+  | 'secondary';
+
 export type BalanceRole = {
-  [roleName in 'Tank' | 'Dps' | 'Support']: [number, boolean, boolean];
+  role: string;
+  rank: number;
+  subRole: SubRole;
+  uncertainty?: number;
+};
+
+export type SubRoleDuplicate = {
+  team: string;
+  role: string;
+  subRole: SubRole;
+};
+
+export type Violation =
+  | { kind: 'duplicatePlayer'; uuid: string }
+  | { kind: 'unknownPlayer'; uuid: string }
+  | { kind: 'missingPlayer'; uuid: string }
+  | { kind: 'inactiveRole'; uuid: string; role: string }
+  | { kind: 'teamTooLarge'; team: string; size: number }
+  | { kind: 'roleOverLimit'; team: string; role: string; count: number }
+  | { kind: 'noCaptain'; team: string; uuid: string }
+  | { kind: 'extraCaptain'; team: string; uuid: string }
+  | { kind: 'leftoverCaptain'; uuid: string }
+  | { kind: 'inconsistentRating'; team: string }
+  | { kind: 'rankLimit'; team: string; role: string }
+  | { kind: 'duplicateSubRole'; team: string; role: string; subRole: SubRole };
+
+export type RolePreference = {
+  uuid: string;
+  name: string;
+  role: string;
+  priority: number | null;
+  isFullFlex: boolean;
+};
+
+export type Preferences = {
+  players: RolePreference[];
+  priorityShares: number[];
+  unlistedShare: number;
+  fullFlexUsed: number;
+};
+
+export type Predictions = {
+  matrix: number[][];
+  vsField: number[];
 };
 
 export type Leftover = {
@@ -22,11 +76,23 @@ export type Balance = {
   dispersion: number;
   leftovers: Leftover[];
   teams: Teams;
+  occurrences: number;
+  duplicates: SubRoleDuplicate[];
+  preferences: Preferences;
+  preferencePenalty: number;
+  predictions: Predictions;
+  repeats: number;
+  rankLimiterRelaxed: boolean;
+  duplicateRolesRelaxed: boolean;
+  violations: Violation[];
+  withinTolerance: boolean | null;
 };
 
 export type BalancerOptions = {
   range: number;
   triesCount: number;
+  resultsCount?: number;
+  minDifference?: number;
   adjustSr: {
     isEnabled: boolean;
     tank: RolePoints;
@@ -39,3 +105,33 @@ export type BalancerOptions = {
 };
 
 export type Results = Balance[];
+
+export type Format = 'players' | 'teams' | 'result' | 'results';
+
+export type Versioned<T> = {
+  version: number;
+  data: T;
+};
+
+export type CurveIssue = {
+  role: string | null;
+  specialization: string | null;
+  error: { kind: string; [field: string]: unknown };
+};
+
+export type RankIssue = {
+  uuid: string;
+  name: string;
+  role: string;
+  rank: number;
+};
+
+export type BalanceError =
+  | { kind: 'invalidCurves'; issues: CurveIssue[] }
+  | { kind: 'outOfScale'; issues: RankIssue[] }
+  | { kind: 'format'; error: FormatError }
+  | { kind: 'invalid'; message: string };
+
+export type FormatError =
+  | { kind: 'unsupportedVersion'; version: number }
+  | { kind: 'invalid'; message: string };
@@ -0,0 +1,57 @@
+export type PairingOptions =
+  | { mode: 'roundRobin'; groups: number }
+  | {
+      mode: 'swiss';
+      number: number;
+      played?: [string, string][];
+      points?: [string, number][];
+      byes?: string[];
+    }
+  | { mode: 'elimination'; double: boolean };
+
+export type Pairing = {
+  home: string;
+  away: string | null;
+  srGap: number;
+};
+
+export type Round = {
+  number: number;
+  pairings: Pairing[];
+  rematches: number;
+};
+
+export type Group = {
+  teams: string[];
+  rounds: Round[];
+};
+
+export type Slot = {
+  kind: 'winnersWinner' | 'winnersLoser' | 'losersWinner';
+  round: number;
+  index: number;
+};
+
+export type BracketMatch = {
+  home: Slot | null;
+  away: Slot | null;
+};
+
+export type LosersRound = {
+  number: number;
+  matches: BracketMatch[];
+};
+
+export type Bracket = {
+  size: number;
+  double: boolean;
+  seeds: string[];
+  firstRound: Round;
+  losersRounds: LosersRound[];
+  grandFinal: BracketMatch | null;
+};
+
+export type Schedule =
+  | { mode: 'roundRobin'; groups: Group[] }
+  | { mode: 'swiss'; round: Round }
+  | { mode: 'elimination'; bracket: Bracket };
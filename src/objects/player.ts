@@ -16,6 +16,8 @@ export type ClassType = {
   isActive: boolean;
   primary: boolean;
   secondary: boolean;
+  deviation?: number;
+  volatility?: number;
 };
 
 export type DescribedClassType = ClassType & { role: string };
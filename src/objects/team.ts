@@ -8,6 +8,7 @@ export type TeamMembers = {
   primary: boolean;
   secondary: boolean;
   role: 'dps' | 'support' | 'tank';
+  uncertainty?: number;
 };
 
 export type Team = {
@@ -15,6 +16,8 @@ export type Team = {
   name: string;
   avgSr: number;
   totalSr: number;
+  rawAvgSr?: number;
+  deviation?: number;
   members: TeamMembers[];
 };
 